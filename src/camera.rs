@@ -0,0 +1,208 @@
+use nalgebra_glm as glm;
+use nalgebra_glm::{Mat3, Mat4x4, Quat, Vec3};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection{
+    Perspective{
+        fov_y: f32,
+        near: f32,
+        far: f32
+    },
+    Orthographic{
+        height: f32,
+        near: f32,
+        far: f32
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Camera{
+    position: Vec3,
+    orientation: Quat,
+    projection: Projection,
+    aspect_ratio: f32
+}
+
+impl Camera {
+    pub fn new(projection:Projection) -> Self{
+        return Self{
+            position: Vec3::zeros(),
+            orientation: Quat::identity(),
+            projection: projection,
+            aspect_ratio: 1.0
+        };
+    }
+
+    pub fn perspective(fov_y:f32, near:f32, far:f32) -> Self{
+        return Self::new(Projection::Perspective{ fov_y, near, far });
+    }
+
+    pub fn orthographic(height:f32, near:f32, far:f32) -> Self{
+        return Self::new(Projection::Orthographic{ height, near, far });
+    }
+
+    pub fn position(&self) -> Vec3{
+        return self.position;
+    }
+
+    pub fn set_position(&mut self, position:Vec3){
+        self.position = position;
+    }
+
+    pub fn orientation(&self) -> Quat{
+        return self.orientation;
+    }
+
+    pub fn set_orientation(&mut self, orientation:Quat){
+        self.orientation = glm::quat_normalize(&orientation);
+    }
+
+    // Keeps the current orientation when the target is the camera position. An up vector parallel to the view is replaced by a perpendicular one.
+    pub fn look_at(&mut self, target:Vec3, up:Vec3){
+        let offset: Vec3 = self.position - target;
+        if offset.norm_squared() <= f32::EPSILON {
+            return;
+        }
+        let back: Vec3 = glm::normalize(&offset);
+        let mut right: Vec3 = glm::cross(&up, &back);
+        if right.norm_squared() <= f32::EPSILON {
+            let fallback_up: Vec3 = if back.y.abs() > 0.99 { Vec3::new(0.0, 0.0, -1.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+            right = glm::cross(&fallback_up, &back);
+        }
+        let right: Vec3 = glm::normalize(&right);
+        let true_up: Vec3 = glm::cross(&back, &right);
+        let rotation: Mat3 = Mat3::from_columns(&[right, true_up, back]);
+        self.orientation = glm::mat3_to_quat(&rotation);
+    }
+
+    pub fn forward(&self) -> Vec3{
+        return glm::quat_rotate_vec3(&self.orientation, &Vec3::new(0.0, 0.0, -1.0));
+    }
+
+    pub fn up(&self) -> Vec3{
+        return glm::quat_rotate_vec3(&self.orientation, &Vec3::new(0.0, 1.0, 0.0));
+    }
+
+    pub fn right(&self) -> Vec3{
+        return glm::quat_rotate_vec3(&self.orientation, &Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    pub fn projection(&self) -> Projection{
+        return self.projection;
+    }
+
+    pub fn set_projection(&mut self, projection:Projection){
+        self.projection = projection;
+    }
+
+    pub fn aspect_ratio(&self) -> f32{
+        return self.aspect_ratio;
+    }
+
+    pub fn set_aspect_ratio(&mut self, aspect_ratio:f32){
+        self.aspect_ratio = aspect_ratio;
+    }
+
+    pub fn view_matrix(&self) -> Mat4x4{
+        return glm::look_at_rh(&self.position, &(self.position + self.forward()), &self.up());
+    }
+
+    // Vulkan clip space has Y pointing down and depth in 0..1, so the Y axis is flipped here.
+    pub fn projection_matrix(&self) -> Mat4x4{
+        let mut projection: Mat4x4 = match self.projection {
+            Projection::Perspective{ fov_y, near, far } =>
+                glm::perspective_rh_zo(self.aspect_ratio, fov_y, near, far),
+            Projection::Orthographic{ height, near, far } => {
+                let half_height = height * 0.5;
+                let half_width = half_height * self.aspect_ratio;
+                glm::ortho_rh_zo(-half_width, half_width, -half_height, half_height, near, far)
+            }
+        };
+        projection[(1, 1)] *= -1.0;
+        return projection;
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4x4{
+        return self.projection_matrix() * self.view_matrix();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nalgebra_glm::Vec4;
+
+    fn assert_close(a:&Vec3, b:&Vec3){
+        assert!(glm::distance(a, b) < 1e-4, "{:?} != {:?}", a, b);
+    }
+
+    fn clip(camera:&Camera, point:Vec3) -> Vec3{
+        let clip: Vec4 = camera.view_projection_matrix() * Vec4::new(point.x, point.y, point.z, 1.0);
+        return clip.xyz() / clip.w;
+    }
+
+    #[test]
+    fn default_camera_looks_down_negative_z(){
+        let camera = Camera::perspective(1.0, 0.1, 100.0);
+        assert_close(&camera.forward(), &Vec3::new(0.0, 0.0, -1.0));
+        assert_close(&camera.up(), &Vec3::new(0.0, 1.0, 0.0));
+        assert_close(&camera.right(), &Vec3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn perspective_depth_spans_zero_to_one(){
+        let camera = Camera::perspective(1.0, 0.1, 100.0);
+        assert!(clip(&camera, Vec3::new(0.0, 0.0, -0.1)).z.abs() < 1e-4);
+        assert!((clip(&camera, Vec3::new(0.0, 0.0, -100.0)).z - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orthographic_depth_spans_zero_to_one(){
+        let camera = Camera::orthographic(10.0, 1.0, 11.0);
+        assert!(clip(&camera, Vec3::new(0.0, 0.0, -1.0)).z.abs() < 1e-5);
+        assert!((clip(&camera, Vec3::new(0.0, 0.0, -6.0)).z - 0.5).abs() < 1e-5);
+        assert!((clip(&camera, Vec3::new(0.0, 0.0, -11.0)).z - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn y_points_down_in_clip_space(){
+        let camera = Camera::orthographic(10.0, 0.1, 100.0);
+        assert!((clip(&camera, Vec3::new(0.0, 5.0, -1.0)).y + 1.0).abs() < 1e-5);
+        assert!((clip(&camera, Vec3::new(0.0, -5.0, -1.0)).y - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn aspect_ratio_widens_the_view(){
+        let mut camera = Camera::orthographic(10.0, 0.1, 100.0);
+        camera.set_aspect_ratio(2.0);
+        assert!((clip(&camera, Vec3::new(10.0, 0.0, -1.0)).x - 1.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn look_at_faces_the_target(){
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        camera.set_position(Vec3::new(0.0, 0.0, 5.0));
+        camera.look_at(Vec3::new(5.0, 0.0, 5.0), Vec3::new(0.0, 1.0, 0.0));
+        assert_close(&camera.forward(), &Vec3::new(1.0, 0.0, 0.0));
+        assert_close(&camera.up(), &Vec3::new(0.0, 1.0, 0.0));
+        assert_close(&camera.right(), &Vec3::new(0.0, 0.0, 1.0));
+        let center: Vec3 = clip(&camera, Vec3::new(5.0, 0.0, 5.0));
+        assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+    }
+
+    #[test]
+    fn look_at_own_position_keeps_the_orientation(){
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        camera.look_at(Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+        assert_eq!(camera.orientation(), Quat::identity());
+    }
+
+    #[test]
+    fn look_at_along_the_up_vector_stays_finite(){
+        let mut camera = Camera::perspective(1.0, 0.1, 100.0);
+        camera.set_position(Vec3::new(0.0, 10.0, 0.0));
+        camera.look_at(Vec3::zeros(), Vec3::new(0.0, 1.0, 0.0));
+        assert_close(&camera.forward(), &Vec3::new(0.0, -1.0, 0.0));
+        assert!(camera.view_projection_matrix().iter().all(|value| value.is_finite()));
+    }
+}
@@ -1,10 +1,18 @@
 use std::sync::Arc;
 use winit::window::Window;
+use crate::renderer::config::RendererConfig;
+use crate::renderer::error::RendererError;
 use crate::renderer::Renderer;
 
 pub mod renderer;
 pub mod material;
+pub mod camera;
+pub mod scene;
 
-pub fn innit_renderer(window:Arc<Window>, present_immediate:bool) -> Renderer{
-    return Renderer::new(window, present_immediate);
+pub fn innit_renderer(window:Arc<Window>, config:RendererConfig) -> Result<Renderer, RendererError>{
+    return Renderer::new(window, &config);
+}
+
+pub fn innit_headless_renderer(width:u32, height:u32, config:RendererConfig) -> Result<Renderer, RendererError>{
+    return Renderer::new_headless(width, height, &config);
 }
\ No newline at end of file
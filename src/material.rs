@@ -1,23 +1,288 @@
-use std::sync::Arc;
-use vulkano::pipeline::GraphicsPipeline;
+pub mod blinn_phong;
+pub mod pbr;
+
+use std::sync::{Arc, PoisonError, RwLock};
+use vulkano::buffer::BufferAccess;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
 use vulkano::shader::ShaderModule;
-use crate::renderer::Renderer;
+use crate::renderer::error::RendererError;
+use crate::renderer::instance::InstanceFormat;
+use crate::renderer::model::{Vertex, VertexFormat};
+use crate::renderer::pipeline_options::PipelineOptions;
+use crate::renderer::shader_loader::ShaderLoadError;
+use crate::renderer::texture::Texture;
+use crate::renderer::{Renderer, ShaderType};
+
+pub const MATERIAL_DESCRIPTOR_SET: u32 = 1;
 
+// Clones share their state, so a pipeline rebuilt after a shader reload is picked up by every copy.
 #[derive(Clone)]
 pub struct Material{
-    pipeline:Arc<GraphicsPipeline>
+    state:Arc<RwLock<MaterialState>>
+}
+
+// Something bound in the material descriptor set. Resource `i` of a material is bound at `layout(set = 1, binding = i)`.
+#[derive(Clone)]
+pub enum MaterialResource{
+    Texture(Texture),
+    UniformBuffer(Arc<dyn BufferAccess>)
+}
+
+struct MaterialState{
+    pipeline:Arc<GraphicsPipeline>,
+    // Depth-only variant used by the shadow pass. `None` when the material casts no shadows.
+    shadow_pipeline:Option<Arc<GraphicsPipeline>>,
+    resources:Vec<MaterialResource>,
+    descriptor_set:Option<Arc<PersistentDescriptorSet>>,
+    options:PipelineOptions,
+    named_shaders:Option<NamedShaders>
+}
+
+struct NamedShaders{
+    vertex_shader:String,
+    fragment_shader:String,
+    vertex_generation:u64,
+    fragment_generation:u64,
+    vertex_input:BuffersDefinition,
+    options:PipelineOptions,
+    gbuffer:bool
 }
 
 impl Material {
-    pub fn new(renderer:&Renderer, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>) -> Self{
+    pub fn new(renderer:&Renderer, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>) -> Result<Self, RendererError>{
+        return Self::with_options(renderer, vertex_shader, fragment_shader, PipelineOptions::default());
+    }
+
+    pub fn with_options(renderer:&Renderer, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>, options:PipelineOptions) -> Result<Self, RendererError>{
+        return Self::for_vertex_format::<Vertex>(renderer, vertex_shader, fragment_shader, options);
+    }
+
+    pub fn for_vertex_format<V: VertexFormat>(renderer:&Renderer, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>, options:PipelineOptions) -> Result<Self, RendererError>{
+        return Self::for_vertex_input(renderer, vertex_shader, fragment_shader, BuffersDefinition::new().vertex::<V>(), false, options);
+    }
+
+    // Binding 0 carries `V` per vertex, binding 1 carries `I` per instance. Used with `DrawCall::instanced`.
+    pub fn for_instanced_format<V: VertexFormat, I: InstanceFormat>(renderer:&Renderer, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>, options:PipelineOptions) -> Result<Self, RendererError>{
+        return Self::for_vertex_input(renderer, vertex_shader, fragment_shader, BuffersDefinition::new().vertex::<V>().instance::<I>(), true, options);
+    }
+
+    fn for_vertex_input(renderer:&Renderer, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>, vertex_input:BuffersDefinition, instanced:bool, options:PipelineOptions) -> Result<Self, RendererError>{
         let pipeline:Arc<GraphicsPipeline>
-            = renderer.build_pipeline(vertex_shader.clone(), fragment_shader.clone());
+            = renderer.build_pipeline(vertex_shader, fragment_shader, vertex_input.clone(), options)?;
+        renderer.set_debug_name(pipeline.as_ref(), "material pipeline");
+        return Ok(Self::from_state(MaterialState{
+            pipeline:pipeline,
+            shadow_pipeline:create_shadow_pipeline(renderer, &vertex_input, instanced, options),
+            resources:Vec::new(),
+            descriptor_set:None,
+            options:options,
+            named_shaders:None
+        }));
+    }
+
+    // Looks the shaders up in the renderer's `ShaderContainer` by name, and rebuilds the pipeline whenever either is reloaded.
+    // On the deferred path the fragment shader's G-buffer variant is used instead, when one is registered.
+    pub fn from_shader_names<V: VertexFormat>(renderer:&Renderer, vertex_shader:&str, fragment_shader:&str, options:PipelineOptions) -> Result<Self, RendererError>{
+        return Self::from_shader_names_with_input(renderer, vertex_shader, fragment_shader, BuffersDefinition::new().vertex::<V>(), false, options);
+    }
+
+    pub fn from_shader_names_instanced<V: VertexFormat, I: InstanceFormat>(renderer:&Renderer, vertex_shader:&str, fragment_shader:&str, options:PipelineOptions) -> Result<Self, RendererError>{
+        return Self::from_shader_names_with_input(renderer, vertex_shader, fragment_shader, BuffersDefinition::new().vertex::<V>().instance::<I>(), true, options);
+    }
+
+    fn from_shader_names_with_input(renderer:&Renderer, vertex_shader:&str, fragment_shader:&str, vertex_input:BuffersDefinition, instanced:bool, options:PipelineOptions) -> Result<Self, RendererError>{
+        let shaders = &renderer.shader_container;
+        let not_found = |shader_type:ShaderType, name:&str| RendererError::ShaderNotFound{
+            shader_type: shader_type,
+            name: String::from(name)
+        };
+        let gbuffer_shader: Option<String> = renderer.gbuffer_shader(fragment_shader, options);
+        let fragment_shader: &str = gbuffer_shader.as_deref().unwrap_or(fragment_shader);
+        let gbuffer: bool = gbuffer_shader.is_some();
+        let pipeline:Arc<GraphicsPipeline> = renderer.build_material_pipeline(
+            shaders.get_shader(ShaderType::Vertex, vertex_shader)
+                .ok_or_else(|| not_found(ShaderType::Vertex, vertex_shader))?,
+            shaders.get_shader(ShaderType::Fragment, fragment_shader)
+                .ok_or_else(|| not_found(ShaderType::Fragment, fragment_shader))?,
+            vertex_input.clone(),
+            options,
+            gbuffer)?;
+        renderer.set_debug_name(pipeline.as_ref(), &format!("material pipeline {}/{}", vertex_shader, fragment_shader));
+
+        return Ok(Self::from_state(MaterialState{
+            pipeline:pipeline,
+            shadow_pipeline:create_shadow_pipeline(renderer, &vertex_input, instanced, options),
+            resources:Vec::new(),
+            descriptor_set:None,
+            options:options,
+            named_shaders:Some(NamedShaders{
+                vertex_shader:String::from(vertex_shader),
+                fragment_shader:String::from(fragment_shader),
+                vertex_generation:shaders.generation(ShaderType::Vertex, vertex_shader).unwrap_or_default(),
+                fragment_generation:shaders.generation(ShaderType::Fragment, fragment_shader).unwrap_or_default(),
+                vertex_input:vertex_input,
+                options:options,
+                gbuffer:gbuffer
+            })
+        }));
+    }
+
+    fn from_state(state:MaterialState) -> Self{
         return Self{
-            pipeline:pipeline
+            state:Arc::new(RwLock::new(state))
         };
     }
 
+    // Texture `i` is bound as a combined image sampler at `layout(set = 1, binding = i)`.
+    pub fn with_textures(self, renderer:&Renderer, textures:Vec<Texture>) -> Result<Self, RendererError>{
+        return self.with_resources(renderer, textures.into_iter().map(MaterialResource::Texture).collect());
+    }
+
+    pub fn with_resources(self, renderer:&Renderer, resources:Vec<MaterialResource>) -> Result<Self, RendererError>{
+        {
+            let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+            state.descriptor_set = Some(create_material_descriptor_set(renderer, &state.pipeline, &resources)?);
+            state.resources = resources;
+        }
+        return Ok(self);
+    }
+
     pub fn pipeline(&self) -> Arc<GraphicsPipeline>{
-        return self.pipeline.clone();
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).pipeline.clone();
+    }
+
+    pub fn textures(&self) -> Vec<Texture>{
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).resources.iter()
+            .filter_map(|resource| match resource {
+                MaterialResource::Texture(texture) => Some(texture.clone()),
+                MaterialResource::UniformBuffer(_) => None
+            })
+            .collect();
     }
+
+    pub fn resources(&self) -> Vec<MaterialResource>{
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).resources.clone();
+    }
+
+    // Whether the pipeline reads the camera's frame data at set 0 binding 0.
+    pub(crate) fn uses_camera(&self) -> bool{
+        return self.pipeline().layout().set_layouts()
+            .get(0)
+            .map_or(false, |layout| layout.bindings().contains_key(&0));
+    }
+
+    // Drawn in the geometry subpass of the deferred path rather than shaded forward.
+    pub fn writes_gbuffer(&self) -> bool{
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).named_shaders.as_ref().map_or(false, |named_shaders| named_shaders.gbuffer);
+    }
+
+    pub(crate) fn shadow_pipeline(&self) -> Option<Arc<GraphicsPipeline>>{
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).shadow_pipeline.clone();
+    }
+
+    pub fn descriptor_set(&self) -> Option<Arc<PersistentDescriptorSet>>{
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).descriptor_set.clone();
+    }
+
+    pub fn options(&self) -> PipelineOptions{
+        return self.state.read().unwrap_or_else(PoisonError::into_inner).options;
+    }
+
+    pub fn is_transparent(&self) -> bool{
+        return self.options().transparent;
+    }
+
+    // Shared by every clone, used to group draw calls by material.
+    pub(crate) fn id(&self) -> usize{
+        return Arc::as_ptr(&self.state) as usize;
+    }
+
+    // Rebuilds the pipeline if one of its named shaders was reloaded. On failure the previous pipeline stays in use.
+    pub(crate) fn refresh(&self, renderer:&Renderer) -> Result<(), ShaderLoadError>{
+        let shaders = &renderer.shader_container;
+        let is_outdated = |named_shaders:&NamedShaders| {
+            shaders.generation(ShaderType::Vertex, &named_shaders.vertex_shader) != Some(named_shaders.vertex_generation)
+                || shaders.generation(ShaderType::Fragment, &named_shaders.fragment_shader) != Some(named_shaders.fragment_generation)
+        };
+
+        if !self.state.read().unwrap_or_else(PoisonError::into_inner).named_shaders.as_ref().map_or(false, is_outdated) {
+            return Ok(());
+        }
+
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        let state = &mut *state;
+        // Another clone may have rebuilt the pipeline between the two locks.
+        let Some(named_shaders) = state.named_shaders.as_mut().filter(|named_shaders| is_outdated(named_shaders)) else {
+            return Ok(());
+        };
+        let (vertex_name, fragment_name) = (named_shaders.vertex_shader.clone(), named_shaders.fragment_shader.clone());
+        let pipeline_error = |message:String| ShaderLoadError::Pipeline{
+            vertex_shader: vertex_name.clone(),
+            fragment_shader: fragment_name.clone(),
+            message
+        };
+
+        let vertex_shader = shaders.get_shader(ShaderType::Vertex, &named_shaders.vertex_shader)
+            .ok_or_else(|| pipeline_error(String::from("vertex shader is no longer registered")))?;
+        let fragment_shader = shaders.get_shader(ShaderType::Fragment, &named_shaders.fragment_shader)
+            .ok_or_else(|| pipeline_error(String::from("fragment shader is no longer registered")))?;
+        let vertex_generation = shaders.generation(ShaderType::Vertex, &named_shaders.vertex_shader).unwrap_or_default();
+        let fragment_generation = shaders.generation(ShaderType::Fragment, &named_shaders.fragment_shader).unwrap_or_default();
+
+        let pipeline = renderer.build_material_pipeline(vertex_shader, fragment_shader, named_shaders.vertex_input.clone(), named_shaders.options, named_shaders.gbuffer)
+            .map_err(|error| pipeline_error(error.to_string()));
+
+        named_shaders.vertex_generation = vertex_generation;
+        named_shaders.fragment_generation = fragment_generation;
+        let pipeline = pipeline?;
+        renderer.set_debug_name(pipeline.as_ref(), &format!("material pipeline {}/{}", vertex_name, fragment_name));
+
+        if !state.resources.is_empty() {
+            state.descriptor_set = Some(create_material_descriptor_set(renderer, &pipeline, &state.resources)
+                .map_err(|error| pipeline_error(error.to_string()))?);
+        }
+        state.pipeline = pipeline;
+        return Ok(());
+    }
+}
+
+// Transparent materials cast no shadows. Neither do materials whose vertex input the shadow shaders can't read, which is only logged.
+fn create_shadow_pipeline(renderer:&Renderer, vertex_input:&BuffersDefinition, instanced:bool, options:PipelineOptions) -> Option<Arc<GraphicsPipeline>>{
+    if options.transparent || !renderer.shadow_settings().enabled {
+        return None;
+    }
+    return match renderer.build_shadow_pipeline(vertex_input.clone(), instanced) {
+        Ok(pipeline) => {
+            renderer.set_debug_name(pipeline.as_ref(), "material shadow pipeline");
+            Some(pipeline)
+        }
+        Err(error) => {
+            log::warn!("material casts no shadows: {}", error);
+            None
+        }
+    };
+}
+
+fn create_material_descriptor_set(renderer:&Renderer, pipeline:&Arc<GraphicsPipeline>, resources:&[MaterialResource]) -> Result<Arc<PersistentDescriptorSet>, RendererError>{
+    let layout = pipeline.layout().set_layouts()
+        .get(MATERIAL_DESCRIPTOR_SET as usize)
+        .ok_or_else(|| RendererError::pipeline("material shaders declare no resources in set 1"))?
+        .clone();
+
+    let writes = resources.iter()
+        .enumerate()
+        .map(|(binding, resource)| match resource {
+            MaterialResource::Texture(texture) =>
+                WriteDescriptorSet::image_view_sampler(binding as u32, texture.view(), texture.sampler()),
+            MaterialResource::UniformBuffer(buffer) =>
+                WriteDescriptorSet::buffer(binding as u32, buffer.clone())
+        });
+
+    return PersistentDescriptorSet::new(
+        &renderer.descriptor_set_allocator,
+        layout,
+        writes,
+    ).map_err(RendererError::allocation);
 }
\ No newline at end of file
@@ -0,0 +1,56 @@
+use bytemuck::{Pod, Zeroable};
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use crate::material::{Material, MaterialResource};
+use crate::renderer::error::RendererError;
+use crate::renderer::model::VertexFormat;
+use crate::renderer::pipeline_options::PipelineOptions;
+use crate::renderer::texture::{SamplerOptions, Texture};
+use crate::renderer::Renderer;
+
+// std140 layout of the `BlinnPhong` uniform block, bound at set 1 binding 0.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Zeroable, Pod)]
+pub struct BlinnPhongParameters{
+    // Multiplied with the diffuse texture. Alpha is only used by transparent materials.
+    pub diffuse_color: [f32; 4],
+    pub specular_color: [f32; 3],
+    pub shininess: f32
+}
+
+impl Default for BlinnPhongParameters {
+    fn default() -> Self{
+        return Self{
+            diffuse_color: [1.0, 1.0, 1.0, 1.0],
+            specular_color: [0.5, 0.5, 0.5],
+            shininess: 32.0
+        };
+    }
+}
+
+impl Material {
+    // Lit by the renderer's lights. `V` needs position, normal and uv at locations 0, 1 and 2, like `TexturedVertex` and `MeshVertex`.
+    pub fn blinn_phong<V: VertexFormat>(renderer:&Renderer, parameters:BlinnPhongParameters, diffuse_texture:Option<Texture>, options:PipelineOptions) -> Result<Material, RendererError>{
+        let material = Material::from_shader_names::<V>(renderer, "blinn_phong", "blinn_phong", options)?;
+
+        let parameter_buffer = CpuAccessibleBuffer::from_data(
+            &renderer.allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            parameters,
+        ).map_err(RendererError::allocation)?;
+        renderer.set_debug_name(parameter_buffer.inner().buffer.as_ref(), "blinn-phong parameters");
+
+        let diffuse_texture = match diffuse_texture {
+            Some(texture) => texture,
+            None => Texture::from_rgba(renderer, 1, 1, &[255, 255, 255, 255], true, SamplerOptions::default())?
+        };
+
+        return material.with_resources(renderer, vec![
+            MaterialResource::UniformBuffer(parameter_buffer),
+            MaterialResource::Texture(diffuse_texture)
+        ]);
+    }
+}
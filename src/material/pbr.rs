@@ -0,0 +1,119 @@
+use bytemuck::{Pod, Zeroable};
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use crate::material::{Material, MaterialResource};
+use crate::renderer::error::RendererError;
+use crate::renderer::gltf_loader::{AlphaMode, ImportedMaterial, ImportedTexture};
+use crate::renderer::model::MeshVertex;
+use crate::renderer::pipeline_options::PipelineOptions;
+use crate::renderer::texture::{SamplerOptions, Texture};
+use crate::renderer::Renderer;
+
+// std140 layout of the `Pbr` uniform block, bound at set 1 binding 0. Factors follow the glTF metallic-roughness model.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Zeroable, Pod)]
+pub struct PbrParameters{
+    pub base_color_factor: [f32; 4],
+    pub emissive_factor: [f32; 3],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub normal_scale: f32,
+    pub occlusion_strength: f32,
+    // Fragments with a lower alpha are discarded. Zero disables alpha testing.
+    pub alpha_cutoff: f32
+}
+
+impl Default for PbrParameters {
+    fn default() -> Self{
+        return Self{
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            emissive_factor: [0.0, 0.0, 0.0],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            normal_scale: 1.0,
+            occlusion_strength: 1.0,
+            alpha_cutoff: 0.0
+        };
+    }
+}
+
+// Missing textures are replaced by 1x1 defaults that leave the factors unchanged.
+// Base color and emissive textures are expected in sRGB, the others in linear formats.
+#[derive(Clone, Default)]
+pub struct PbrTextures{
+    pub base_color: Option<Texture>,
+    // Roughness in the green channel, metalness in the blue channel.
+    pub metallic_roughness: Option<Texture>,
+    pub normal: Option<Texture>,
+    // Occlusion in the red channel.
+    pub occlusion: Option<Texture>,
+    pub emissive: Option<Texture>
+}
+
+impl Material {
+    // Bindings in set 1: 0 parameters, 1 base color, 2 metallic-roughness, 3 normal, 4 occlusion, 5 emissive.
+    pub fn pbr(renderer:&Renderer, parameters:PbrParameters, textures:PbrTextures, options:PipelineOptions) -> Result<Material, RendererError>{
+        let material = Material::from_shader_names::<MeshVertex>(renderer, "pbr", "pbr", options)?;
+
+        let parameter_buffer = CpuAccessibleBuffer::from_data(
+            &renderer.allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            parameters,
+        ).map_err(RendererError::allocation)?;
+        renderer.set_debug_name(parameter_buffer.inner().buffer.as_ref(), "pbr parameters");
+
+        let white = [255, 255, 255, 255];
+        // Tangent space +Z, i.e. the unperturbed surface normal.
+        let flat_normal = [128, 128, 255, 255];
+        let or_default = |texture:Option<Texture>, pixel:&[u8; 4], srgb:bool| match texture {
+            Some(texture) => Ok(texture),
+            None => Texture::from_rgba(renderer, 1, 1, pixel, srgb, SamplerOptions::default())
+        };
+
+        return material.with_resources(renderer, vec![
+            MaterialResource::UniformBuffer(parameter_buffer),
+            MaterialResource::Texture(or_default(textures.base_color, &white, true)?),
+            MaterialResource::Texture(or_default(textures.metallic_roughness, &white, false)?),
+            MaterialResource::Texture(or_default(textures.normal, &flat_normal, false)?),
+            MaterialResource::Texture(or_default(textures.occlusion, &white, false)?),
+            MaterialResource::Texture(or_default(textures.emissive, &white, true)?)
+        ]);
+    }
+
+    // `textures` is `GltfScene::textures`. Blended materials are made transparent, masked ones get an alpha cutoff.
+    pub fn pbr_from_imported(renderer:&Renderer, material:&ImportedMaterial, textures:&[ImportedTexture], sampler_options:SamplerOptions) -> Result<Material, RendererError>{
+        let upload = |index:Option<usize>, srgb:bool| -> Result<Option<Texture>, RendererError> {
+            return match index.and_then(|index| textures.get(index)) {
+                Some(texture) => Ok(Some(Texture::from_imported(renderer, texture, srgb, sampler_options)?)),
+                None => Ok(None)
+            };
+        };
+
+        let parameters = PbrParameters{
+            base_color_factor: material.base_color_factor,
+            emissive_factor: material.emissive_factor,
+            metallic_factor: material.metallic_factor,
+            roughness_factor: material.roughness_factor,
+            normal_scale: material.normal_scale,
+            occlusion_strength: material.occlusion_strength,
+            alpha_cutoff: match material.alpha_mode {
+                AlphaMode::Mask{ cutoff } => cutoff,
+                AlphaMode::Opaque | AlphaMode::Blend => 0.0
+            }
+        };
+
+        let pbr_textures = PbrTextures{
+            base_color: upload(material.base_color_texture, true)?,
+            metallic_roughness: upload(material.metallic_roughness_texture, false)?,
+            normal: upload(material.normal_texture, false)?,
+            occlusion: upload(material.occlusion_texture, false)?,
+            emissive: upload(material.emissive_texture, true)?
+        };
+
+        let options = PipelineOptions::default().transparent(material.alpha_mode == AlphaMode::Blend);
+        return Material::pbr(renderer, parameters, pbr_textures, options);
+    }
+}
@@ -1,9 +1,27 @@
-pub(crate) mod shader_loader;
+pub mod shader_loader;
+pub mod bounds;
+pub mod config;
+pub mod debug;
+pub mod deferred;
 pub mod draw_call;
+pub mod frame;
+pub mod error;
+pub mod gltf_loader;
+pub mod instance;
+pub mod light;
 pub mod model;
+pub mod obj_loader;
+pub mod offscreen;
+pub mod pipeline_options;
+pub mod post_process;
+pub mod render_queue;
+pub mod shadow;
+pub mod texture;
 
+use std::path::Path;
 use std::sync::Arc;
-use nalgebra_glm::Mat4x4;
+use nalgebra_glm as glm;
+use nalgebra_glm::{Mat4x4, Vec3};
 
 use vulkano::{
     buffer::TypedBufferAccess,
@@ -12,13 +30,16 @@ use vulkano::{
         RenderPassBeginInfo, SubpassContents,
     },
     device::{
-        Device, DeviceCreateInfo, DeviceExtensions, physical::PhysicalDeviceType, QueueCreateInfo,
+        Device, DeviceCreateInfo, DeviceExtensions, Features, physical::{PhysicalDevice, PhysicalDeviceType}, QueueCreateInfo,
     },
-    image::{ImageAccess, ImageUsage, SwapchainImage, view::ImageView},
+    image::{AttachmentImage, ImageAccess, ImageUsage, SampleCount, SwapchainImage, view::{ImageView, ImageViewAbstract}},
     instance::{Instance, InstanceCreateInfo},
     pipeline::{
         graphics::{
+            depth_stencil::{CompareOp, DepthState, DepthStencilState},
+            color_blend::ColorBlendState,
             input_assembly::InputAssemblyState,
+            multisample::MultisampleState,
             vertex_input::BuffersDefinition,
             viewport::{Viewport, ViewportState},
         },
@@ -32,184 +53,226 @@ use vulkano::{
     sync::{self, FlushError, GpuFuture},
     VulkanLibrary,
 };
-use vulkano::buffer::{BufferUsage, CpuBufferPool};
-use vulkano::command_buffer::PrimaryAutoCommandBuffer;
+use vulkano::command_buffer::{CopyImageToBufferInfo, PrimaryAutoCommandBuffer};
+use vulkano::format::ClearValue;
 use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
-use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
 use vulkano::device::Queue;
-use vulkano::memory::allocator::{MemoryUsage, StandardMemoryAllocator};
-use vulkano::pipeline::{Pipeline, PipelineBindPoint};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::{Pipeline, PipelineBindPoint, StateMode};
 use vulkano::shader::ShaderModule;
-use vulkano::swapchain::{PresentMode, Surface, SwapchainAcquireFuture};
-use vulkano::buffer::BufferContents;
+use vulkano::swapchain::{ColorSpace, CompositeAlpha, PresentMode, Surface, SurfaceCapabilities, SwapchainAcquireFuture};
+use bytemuck::{Pod, Zeroable};
+use vulkano::format::Format;
+use vulkano::instance::InstanceExtensions;
+use vulkano::instance::debug::DebugUtilsMessenger;
+use vulkano::device::DeviceOwned;
+use vulkano::VulkanObject;
 use vulkano_win::create_surface_from_winit;
 use winit::window::Window;
+use crate::camera::Camera;
+use crate::material::MATERIAL_DESCRIPTOR_SET;
+use crate::renderer::bounds::Frustum;
+use crate::renderer::config::{RenderPath, RendererConfig};
+use crate::renderer::debug::{create_debug_messenger, debug_instance_requirements, set_debug_name};
+use crate::renderer::deferred::{create_deferred_render_pass, DeferredLighting, FORWARD_SUBPASS, GBUFFER_COLOR_ATTACHMENTS, GBUFFER_SHADER_SUFFIX, GBuffer, GEOMETRY_SUBPASS};
 use crate::renderer::draw_call::DrawCall;
-use crate::renderer::model::Vertex;
-use crate::renderer::shader_loader::ShaderContainer;
+use crate::renderer::error::RendererError;
+use crate::renderer::frame::FrameResources;
+use crate::renderer::light::{Light, LightData};
+use crate::renderer::model::IndexBuffer;
+use crate::renderer::offscreen::{OFFSCREEN_FORMAT, OffscreenTarget};
+use crate::renderer::pipeline_options::PipelineOptions;
+use crate::renderer::post_process::{Exposure, PostProcessChain, PostProcessor};
+use crate::renderer::render_queue::{FrameStatistics, sort_draw_calls};
+use crate::renderer::shader_loader::{ShaderContainer, ShaderLoadError};
+use crate::renderer::shadow::{create_shadow_render_pass, plan_shadows, ShadowMap, ShadowSettings, ShadowView};
 
 pub struct Renderer{
     pub device: Arc<Device>,
     pub shader_container: ShaderContainer,
     pub(crate) allocator:StandardMemoryAllocator,
-    render_surface: Arc<Surface>,
-    swapchain_container: SwapchainContainer,
+    render_target: RenderTarget,
     render_pass: Arc<RenderPass>,
-    queue: Arc<Queue>,
+    shadow_render_pass: Arc<RenderPass>,
+    shadow_settings: ShadowSettings,
+    samples: SampleCount,
+    pub(crate) queue: Arc<Queue>,
     viewport: Viewport,
-    framebuffers: Vec<Arc<Framebuffer>>,
-    command_buffer_allocator: StandardCommandBufferAllocator,
-    descriptor_set_allocator: StandardDescriptorSetAllocator,
-    uniform_buffer: CpuBufferPool<UniformData>,
-    previous_frame_end: Option<Box<dyn GpuFuture>>
+    // The scene is rendered into an intermediate image that post-processing then writes to the target.
+    scene_framebuffer: Arc<Framebuffer>,
+    post_processor: PostProcessor,
+    post_process_chain: PostProcessChain,
+    // Only set on the deferred render path.
+    deferred_lighting: Option<DeferredLighting>,
+    pub(crate) command_buffer_allocator: StandardCommandBufferAllocator,
+    pub(crate) descriptor_set_allocator: StandardDescriptorSetAllocator,
+    frames: Vec<FrameResources>,
+    frame_index: usize,
+    sort_by_depth: bool,
+    frustum_culling: bool,
+    lights: Vec<Light>,
+    ambient_light: Vec3,
+    frame_statistics: FrameStatistics,
+    shader_errors: Vec<ShaderLoadError>,
+    // Kept alive for as long as the renderer, messages stop once it is dropped.
+    _debug_messenger: Option<DebugUtilsMessenger>
+}
+
+const DEPTH_FORMAT: Format = Format::D16_UNORM;
+
+enum RenderTarget{
+    Swapchain(SwapchainContainer),
+    Offscreen(OffscreenTarget)
 }
 
 struct SwapchainContainer{
+    pub surface: Arc<Surface>,
     pub swapchain: Arc<Swapchain>,
     pub images: Vec<Arc<SwapchainImage>>,
     pub optimal: bool
 }
 
-#[derive(PartialEq, Eq, Clone)]
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum ShaderType{
     Vertex,
     Fragment
 }
 
-#[derive(BufferContents)]
 #[repr(C)]
-struct UniformData{
-    transformation: Mat4x4
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub(crate) struct FrameData{
+    view: Mat4x4,
+    projection: Mat4x4,
+    camera_position: [f32; 4],
+    // Lets full-screen passes reconstruct world positions from depth.
+    inverse_view_projection: Mat4x4
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct ObjectData{
+    transform: Mat4x4
 }
 
 impl Renderer{
-    pub fn new(window: Arc<Window>, present_immediate:bool) -> Self {
-        let library = VulkanLibrary::new().unwrap();
+    pub fn new(window: Arc<Window>, config:&RendererConfig) -> Result<Self, RendererError> {
+        let library = VulkanLibrary::new()?;
         let required_extensions = vulkano_win::required_extensions(&library);
+        let (instance, debug_messenger) = create_instance(library, required_extensions, config)?;
 
-        let instance = Instance::new(
-            library,
-            InstanceCreateInfo {
-                enabled_extensions: required_extensions,
-                enumerate_portability: true,
-                ..Default::default()
-            },
-        ).unwrap();
-
-        let surface = create_surface_from_winit(window, instance.clone()).unwrap();
+        let surface = create_surface_from_winit(window, instance.clone())?;
 
         let device_extensions = DeviceExtensions {
             khr_swapchain: true,
             ..DeviceExtensions::empty()
-        };
+        }.union(&config.device_extensions);
 
-        let (physical_device, queue_family_index) = instance
-            .enumerate_physical_devices().unwrap()
-            .filter(|p| {
-                p.supported_extensions().contains(&device_extensions)
-            })
-            .filter_map(|p| {
-                p.queue_family_properties()
-                    .iter()
-                    .enumerate()
-                    .position(|(i, q)| {
-                        q.queue_flags.graphics && p.surface_support(i as u32, &surface).unwrap_or(false)
-                    })
-                    .map(|i| (p, i as u32))
-            })
-            .min_by_key(|(p, _)| {
-                match p.properties().device_type {
-                    PhysicalDeviceType::DiscreteGpu => 0,
-                    PhysicalDeviceType::IntegratedGpu => 1,
-                    PhysicalDeviceType::VirtualGpu => 2,
-                    PhysicalDeviceType::Cpu => 3,
-                    PhysicalDeviceType::Other => 4,
-                    _ => 5,
-                }
-            }).expect("No suitable physical device found");
-
-        let (device, mut queues) = Device::new(
-            physical_device,
-            DeviceCreateInfo {
-                enabled_extensions: device_extensions,
-                queue_create_infos: vec![QueueCreateInfo {
-                    queue_family_index,
-                    ..Default::default()
-                }],
-                ..Default::default()
-            },
-        ).unwrap();
+        let (physical_device, queue_family_index) =
+            select_physical_device(&instance, &device_extensions, config, Some(&surface))?;
 
-        let queue: Arc<Queue> = queues.next().unwrap();
+        let (device, queue) = create_device(physical_device, queue_family_index, device_extensions, config.device_features)?;
 
         let (swapchain, images) = {
-            let surface_capabilities = device
-                .physical_device()
+            let physical_device = device.physical_device();
+            let surface_capabilities = physical_device
                 .surface_capabilities(&surface, Default::default())
-                .unwrap();
-
-            let image_format = Some(
-                device
-                    .physical_device()
-                    .surface_formats(&surface, Default::default())
-                    .unwrap()[0].0,
-            );
+                .map_err(RendererError::swapchain)?;
+            let surface_formats = physical_device
+                .surface_formats(&surface, Default::default())
+                .map_err(RendererError::swapchain)?;
+            let present_modes: Vec<PresentMode> = physical_device
+                .surface_present_modes(&surface)
+                .map_err(RendererError::swapchain)?
+                .collect();
 
-            let window = surface.object().unwrap().downcast_ref::<Window>().unwrap();
+            let (image_format, image_color_space) = choose_surface_format(&surface_formats, config)?;
+            let window = surface_window(&surface)?;
 
             Swapchain::new(
                 device.clone(),
                 surface.clone(),
                 SwapchainCreateInfo {
-                    min_image_count: surface_capabilities.min_image_count,
-                    image_format: image_format,
-                    present_mode: if present_immediate{ PresentMode::Immediate }else{ PresentMode::Fifo },
+                    min_image_count: choose_image_count(&surface_capabilities, config),
+                    image_format: Some(image_format),
+                    image_color_space: image_color_space,
+                    present_mode: choose_present_mode(&present_modes, config),
                     image_extent: window.inner_size().into(),
                     image_usage: ImageUsage {
                         color_attachment: true,
                         ..ImageUsage::empty()
                     },
-                    composite_alpha: surface_capabilities
-                        .supported_composite_alpha
-                        .iter()
-                        .next()
-                        .unwrap(),
+                    composite_alpha: choose_composite_alpha(&surface_capabilities, config)?,
                     ..Default::default()
                 },
-            ).unwrap()
+            ).map_err(RendererError::swapchain)?
         };
 
-        let shader_container: ShaderContainer = ShaderContainer::load(device.clone()).unwrap();
-
-        let render_pass: Arc<RenderPass> = vulkano::single_pass_renderpass!(
-            device.clone(),
-            attachments: {
-                color: {
-                    load: Clear,
-                    store: Store,
-                    format: swapchain.image_format(),
-                    samples: 1,
-                }
-            },
-            pass: {
-                color: [color],
-                depth_stencil: {}
-            }).unwrap();
-
         let swapchain_container: SwapchainContainer =
             SwapchainContainer{
+                surface: surface.clone(),
                 swapchain: swapchain.clone(),
                 images: images.clone(),
                 optimal: true
             };
 
+        let allocator = StandardMemoryAllocator::new_default(device.clone());
+        return Self::assemble(device, queue, allocator, RenderTarget::Swapchain(swapchain_container), debug_messenger, config);
+    }
+
+    pub fn new_headless(width:u32, height:u32, config:&RendererConfig) -> Result<Self, RendererError> {
+        if width == 0 || height == 0 {
+            return Err(RendererError::InvalidExtent{ width: width, height: height });
+        }
+
+        let library = VulkanLibrary::new()?;
+        let (instance, debug_messenger) = create_instance(library, InstanceExtensions::empty(), config)?;
+
+        let device_extensions = config.device_extensions;
+
+        let (physical_device, queue_family_index) =
+            select_physical_device(&instance, &device_extensions, config, None)?;
+
+        let (device, queue) = create_device(physical_device, queue_family_index, device_extensions, config.device_features)?;
+
+        let allocator = StandardMemoryAllocator::new_default(device.clone());
+        let offscreen_target: OffscreenTarget = OffscreenTarget::new(&allocator, [width, height])?;
+
+        return Self::assemble(device, queue, allocator, RenderTarget::Offscreen(offscreen_target), debug_messenger, config);
+    }
+
+    fn assemble(device: Arc<Device>, queue: Arc<Queue>, allocator: StandardMemoryAllocator, render_target: RenderTarget, debug_messenger: Option<DebugUtilsMessenger>, config: &RendererConfig) -> Result<Self, RendererError> {
+        // A built-in shader the device rejects is a shader problem, not a device failure.
+        let shader_container: ShaderContainer = ShaderContainer::load(device.clone())
+            .map_err(RendererError::pipeline)?;
+
+        // The G-buffer is read per sample-less texel, so the deferred path renders without MSAA.
+        let samples: SampleCount = match config.render_path {
+            RenderPath::Forward => clamp_sample_count(device.physical_device(), config.samples),
+            RenderPath::Deferred => SampleCount::Sample1
+        };
+        let mut post_processor = PostProcessor::new(device.clone(), config.hdr, render_target.format(), config.exposure)?;
+        let render_pass: Arc<RenderPass> = match config.render_path {
+            RenderPath::Forward => create_render_pass(device.clone(), post_processor.scene_format(), samples)?,
+            RenderPath::Deferred => create_deferred_render_pass(device.clone(), post_processor.scene_format(), DEPTH_FORMAT)?
+        };
+        let mut deferred_lighting: Option<DeferredLighting> = match config.render_path {
+            RenderPath::Forward => None,
+            RenderPath::Deferred => Some(DeferredLighting::new(device.clone(), &shader_container, render_pass.clone())?)
+        };
+        let shadow_render_pass: Arc<RenderPass> = create_shadow_render_pass(device.clone())?;
+
         let mut viewport = Viewport {
             origin: [0.0, 0.0],
             dimensions: [0.0, 0.0],
             depth_range: 0.0..1.0,
         };
 
-        let framebuffers: Vec<Arc<Framebuffer>> = window_size_dependent_setup(&swapchain_container.images, render_pass.clone(), &mut viewport);
+        let scene = match &render_target {
+            RenderTarget::Swapchain(swapchain_container) => post_processor.resize(&allocator, &swapchain_container.images)?,
+            RenderTarget::Offscreen(offscreen_target) => post_processor.resize(&allocator, &[offscreen_target.image.clone()])?
+        };
+        let (scene_framebuffer, gbuffer) =
+            window_size_dependent_setup(&allocator, scene, render_pass.clone(), &mut viewport, None, samples, config.render_path)?;
 
         let command_buffer_allocator =
             StandardCommandBufferAllocator::new(device.clone(), Default::default());
@@ -217,192 +280,997 @@ impl Renderer{
         let descriptor_set_allocator =
             StandardDescriptorSetAllocator::new(device.clone());
 
-        let previous_frame_end = Some(sync::now(device.clone()).boxed());
+        if let (Some(deferred_lighting), Some(gbuffer)) = (&mut deferred_lighting, &gbuffer) {
+            deferred_lighting.set_gbuffer(&descriptor_set_allocator, gbuffer)?;
+        }
 
-        let uniform_buffer: CpuBufferPool<UniformData> = CpuBufferPool::<UniformData>::new(
-            Arc::new(StandardMemoryAllocator::new_default(device.clone())),
-            BufferUsage {
-                uniform_buffer: true,
-                ..BufferUsage::empty()
-            },
-            MemoryUsage::Upload,
-        );
+        let frames: Vec<FrameResources> = (0..config.frames_in_flight.max(1))
+            .map(|_| {
+                let shadow_map = ShadowMap::new(&allocator, &shadow_render_pass, &config.shadows, queue.queue_family_index())?;
+                return FrameResources::new(&allocator, shadow_map);
+            })
+            .collect::<Result<Vec<_>, RendererError>>()?;
 
-        return Self{
+        return Ok(Self{
             device: device.clone(),
             shader_container: shader_container,
-            render_surface: surface.clone(),
-            swapchain_container: swapchain_container,
+            render_target: render_target,
             render_pass: render_pass.clone(),
+            shadow_render_pass: shadow_render_pass,
+            shadow_settings: config.shadows,
+            samples: samples,
             queue: queue.clone(),
             viewport: viewport,
-            framebuffers: framebuffers,
-            allocator:StandardMemoryAllocator::new_default(device.clone()),
+            scene_framebuffer: scene_framebuffer,
+            post_processor: post_processor,
+            post_process_chain: PostProcessChain::new(),
+            deferred_lighting: deferred_lighting,
+            allocator:allocator,
             command_buffer_allocator: command_buffer_allocator,
             descriptor_set_allocator: descriptor_set_allocator,
-            uniform_buffer: uniform_buffer,
-            previous_frame_end: previous_frame_end
-        }
+            frames: frames,
+            frame_index: 0,
+            sort_by_depth: config.sort_by_depth,
+            frustum_culling: config.frustum_culling,
+            lights: Vec::new(),
+            ambient_light: Vec3::new(0.03, 0.03, 0.03),
+            frame_statistics: FrameStatistics::default(),
+            shader_errors: Vec::new(),
+            _debug_messenger: debug_messenger
+        });
     }
 
     pub fn on_resized(&mut self) {
-        self.swapchain_container.optimal = false;
+        if let RenderTarget::Swapchain(swapchain_container) = &mut self.render_target {
+            swapchain_container.optimal = false;
+        }
     }
 
-    pub fn submit_frame(&mut self, draw_calls:Vec<DrawCall>, block_until_drawn:bool){
-        let window = self.render_surface.object().unwrap().downcast_ref::<Window>().unwrap();
-        let dimensions = window.inner_size();
-        if dimensions.width == 0 || dimensions.height == 0 {
-            return;
-        }
+    pub fn offscreen_target(&self) -> Option<&OffscreenTarget>{
+        return match &self.render_target {
+            RenderTarget::Offscreen(offscreen_target) => Some(offscreen_target),
+            RenderTarget::Swapchain(_) => None
+        };
+    }
 
-        self.previous_frame_end.as_mut().unwrap().cleanup_finished();
+    // The sample count actually in use, after clamping the configured one to what the device supports.
+    pub fn samples(&self) -> SampleCount{
+        return self.samples;
+    }
 
-        if !self.swapchain_container.optimal {
-            let (new_swapchain, new_images) =
-                match self.swapchain_container.swapchain.recreate(SwapchainCreateInfo {
-                    image_extent: dimensions.into(),
-                    ..self.swapchain_container.swapchain.create_info()
-                }) {
-                    Ok(r) => r,
-                    Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return,
-                    Err(e) => panic!("Failed to recreate swapchain: {:?}", e),
-                };
+    pub fn frames_in_flight(&self) -> usize{
+        return self.frames.len();
+    }
 
-            self.swapchain_container = SwapchainContainer{
-                swapchain:new_swapchain.clone(),
-                images: new_images.clone(),
-                optimal: true
-            };
+    // The slot the next `submit_frame` records into, in `0..frames_in_flight()`.
+    // Callers can use it to index their own per-frame resources.
+    pub fn frame_index(&self) -> usize{
+        return self.frame_index;
+    }
 
-            self.framebuffers = window_size_dependent_setup(
-                &new_images,
-                self.render_pass.clone(),
-                &mut self.viewport,
-            );
-        }
+    // Used by every following frame until replaced. Only the first `MAX_LIGHTS` lights are uploaded.
+    pub fn set_lights(&mut self, lights:Vec<Light>){
+        self.lights = lights;
+    }
 
-        let (image_index, suboptimal, image_acquire_future) =
-            match acquire_next_image(self.swapchain_container.swapchain.clone(), None) {
-                Ok(r) => r,
-                Err(AcquireError::OutOfDate) => {
-                    self.swapchain_container.optimal = false;
-                    return;
-                }
-                Err(e) => panic!("Failed to acquire next image: {:?}", e),
-            };
-        if suboptimal {
-            self.swapchain_container.optimal = false;
+    pub fn lights(&self) -> &[Light]{
+        return &self.lights;
+    }
+
+    pub fn set_ambient_light(&mut self, ambient_light:Vec3){
+        self.ambient_light = ambient_light;
+    }
+
+    pub fn render_path(&self) -> RenderPath{
+        return match self.deferred_lighting {
+            Some(_) => RenderPath::Deferred,
+            None => RenderPath::Forward
+        };
+    }
+
+    pub fn shadow_settings(&self) -> ShadowSettings{
+        return self.shadow_settings;
+    }
+
+    // Effects run on the rendered scene in order before it reaches the target.
+    pub fn post_processing(&self) -> &PostProcessChain{
+        return &self.post_process_chain;
+    }
+
+    // Effects can be added, reordered and toggled between frames.
+    pub fn post_processing_mut(&mut self) -> &mut PostProcessChain{
+        return &mut self.post_process_chain;
+    }
+
+    pub fn exposure(&self) -> Exposure{
+        return self.post_processor.exposure();
+    }
+
+    // Switching to automatic exposure starts adapting from the next frame's luminance.
+    pub fn set_exposure(&mut self, exposure:Exposure){
+        self.post_processor.set_exposure(exposure);
+    }
+
+    // Bind and draw counts of the most recently recorded frame.
+    pub fn frame_statistics(&self) -> FrameStatistics{
+        return self.frame_statistics;
+    }
+
+    pub fn aspect_ratio(&self) -> f32{
+        return self.viewport.dimensions[0] / self.viewport.dimensions[1];
+    }
+
+    pub fn submit_frame(&mut self, camera:&mut Camera, mut draw_calls:Vec<DrawCall>, block_until_drawn:bool) -> Result<(), RendererError>{
+        // The CPU may run ahead of the GPU by up to `frames_in_flight` frames, this slot's previous frame must be done first.
+        let frame_index = self.frame_index;
+        self.frames[frame_index].wait()?;
+
+        if self.shader_container.is_watching() {
+            let mut errors = self.reload_changed_shaders();
+            self.shader_errors.append(&mut errors);
+        }
+        for draw_call in &draw_calls {
+            if let Err(error) = draw_call.material.refresh(self) {
+                self.shader_errors.push(error);
+            }
         }
 
+        let acquired_image: Option<(u32, SwapchainAcquireFuture)> = match self.render_target {
+            RenderTarget::Swapchain(_) => match self.acquire_swapchain_image(camera)? {
+                Some(acquired_image) => Some(acquired_image),
+                None => return Ok(())
+            },
+            RenderTarget::Offscreen(_) => None
+        };
+        let framebuffer_index: usize = match &acquired_image {
+            Some((image_index, _)) => *image_index as usize,
+            None => 0
+        };
+
+        // Resizes update the camera as well, this covers cameras that were created or swapped in since the last one.
+        camera.set_aspect_ratio(self.aspect_ratio());
+        let camera_position: Vec3 = camera.position();
+        let frame_data = FrameData{
+            view: camera.view_matrix(),
+            projection: camera.projection_matrix(),
+            camera_position: [camera_position.x, camera_position.y, camera_position.z, 1.0],
+            inverse_view_projection: glm::inverse(&camera.view_projection_matrix())
+        };
+        let mut light_data = LightData::new(&self.lights, self.ambient_light);
+        let (shadow_views, shadow_data) = plan_shadows(&self.lights, camera, &self.shadow_settings, &mut light_data);
+        self.frames[frame_index].write_frame_data(frame_data, light_data, shadow_data)?;
+
         let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
             &self.command_buffer_allocator,
             self.queue.queue_family_index(),
             CommandBufferUsage::OneTimeSubmit,
-        ).unwrap();
-
+        ).map_err(RendererError::submission)?;
 
-        let uniform_data = UniformData{
-            transformation: Mat4x4::identity()
-        };
-        let uniform_buffer_subbuffer=  self.uniform_buffer.from_data(uniform_data).unwrap();
-
-        let uniform_descriptors = PersistentDescriptorSet::new(
-            &self.descriptor_set_allocator,
-            layout.clone(),
-            [WriteDescriptorSet::buffer(0, uniform_buffer_subbuffer)],
-        ).unwrap();
+        let mut statistics = FrameStatistics::default();
+        self.record_shadow_passes(&mut command_buffer_builder, &self.frames[frame_index].shadow_map, &shadow_views, &draw_calls, &mut statistics)?;
 
         command_buffer_builder
             .begin_render_pass(
                 RenderPassBeginInfo {
-                    clear_values: vec![Some([1.0, 0.0, 0.0, 1.0].into())],
+                    clear_values: self.clear_values(),
                     ..RenderPassBeginInfo::framebuffer(
-                        self.framebuffers[image_index as usize].clone(),
+                        self.scene_framebuffer.clone(),
                     )
                 },
                 SubpassContents::Inline,
-            ).unwrap()
-            .set_viewport(0, [self.viewport.clone()])
-            .bind_descriptor_sets(
-                PipelineBindPoint::Graphics,
-                draw_calls.first().unwrap().pipeline().layout().clone(),
-                0,
-                uniform_descriptors);
+            ).map_err(RendererError::submission)?
+            .set_viewport(0, [self.viewport.clone()]);
 
-            for draw_call in draw_calls {
-                command_buffer_builder
-                    .bind_pipeline_graphics(draw_call.material.pipeline())
-                    .bind_vertex_buffers(0, draw_call.model.buffer.clone())
-                    .draw(draw_call.model.buffer.len() as u32, 1, 0, 0).unwrap();
-            }
+        if self.frustum_culling {
+            let frustum = Frustum::from_view_projection(&camera.view_projection_matrix());
+            let submitted = draw_calls.len();
+            draw_calls.retain(|draw_call| draw_call.is_visible(&frustum));
+            statistics.culled = (submitted - draw_calls.len()) as u32;
+        }
+
+        sort_draw_calls(&mut draw_calls, &camera_position, self.sort_by_depth);
+
+        // Sorting first keeps the state grouping within each subpass.
+        let (gbuffer_draw_calls, forward_draw_calls): (Vec<DrawCall>, Vec<DrawCall>) = draw_calls
+            .into_iter()
+            .partition(|draw_call| draw_call.material.writes_gbuffer());
+
+        if self.deferred_lighting.is_some() {
+            self.record_draw_calls(&mut command_buffer_builder, frame_index, gbuffer_draw_calls, &mut statistics)?;
+            command_buffer_builder
+                .next_subpass(SubpassContents::Inline).map_err(RendererError::submission)?;
+            self.record_lighting_pass(&mut command_buffer_builder, frame_index)?;
+            command_buffer_builder
+                .next_subpass(SubpassContents::Inline).map_err(RendererError::submission)?;
+        }
+        self.record_draw_calls(&mut command_buffer_builder, frame_index, forward_draw_calls, &mut statistics)?;
 
         command_buffer_builder
-            .end_render_pass().unwrap();
+            .end_render_pass().map_err(RendererError::submission)?;
+        self.frame_statistics = statistics;
+
+        self.post_processor.record(
+            &mut command_buffer_builder,
+            &self.post_process_chain,
+            &self.shader_container,
+            &self.descriptor_set_allocator,
+            framebuffer_index,
+        )?;
+
+        if let RenderTarget::Offscreen(offscreen_target) = &self.render_target {
+            command_buffer_builder
+                .copy_image_to_buffer(CopyImageToBufferInfo::image_buffer(
+                    offscreen_target.image.clone(),
+                    offscreen_target.readback_buffer.clone(),
+                )).map_err(RendererError::submission)?;
+        }
+
+        let command_buffer = command_buffer_builder.build().map_err(RendererError::submission)?;
+        let result = match acquired_image {
+            Some((image_index, image_acquire_future)) =>
+                self.submit_command_buffer(command_buffer, image_acquire_future, image_index, block_until_drawn),
+            None =>
+                self.submit_offscreen_command_buffer(command_buffer)
+        };
+        self.frame_index = (frame_index + 1) % self.frames.len();
+        return result;
+    }
+
+    // On the deferred path the pipeline draws in the forward subpass, after lighting.
+    pub fn build_pipeline(&self, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>, vertex_input:BuffersDefinition, options:PipelineOptions) -> Result<Arc<GraphicsPipeline>, RendererError>{
+        return self.build_material_pipeline(vertex_shader, fragment_shader, vertex_input, options, false);
+    }
+
+    // With `gbuffer` the fragment shader writes the G-buffer in the geometry subpass of the deferred path.
+    pub(crate) fn build_material_pipeline(&self, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>, vertex_input:BuffersDefinition, options:PipelineOptions, gbuffer:bool) -> Result<Arc<GraphicsPipeline>, RendererError>{
+        let depth_state: Option<DepthState> = if options.depth_test || options.depth_write {
+            Some(DepthState {
+                enable_dynamic: false,
+                write_enable: StateMode::Fixed(options.depth_write),
+                compare_op: StateMode::Fixed(if options.depth_test { CompareOp::Less } else { CompareOp::Always }),
+            })
+        } else {
+            None
+        };
+
+        let vertex_entry_point = vertex_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("vertex shader has no `main` entry point"))?;
+        let fragment_entry_point = fragment_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("fragment shader has no `main` entry point"))?;
+
+        let subpass_index: u32 = match (&self.deferred_lighting, gbuffer) {
+            (Some(_), true) => GEOMETRY_SUBPASS,
+            (Some(_), false) => FORWARD_SUBPASS,
+            (None, _) => 0
+        };
+        let subpass = Subpass::from(self.render_pass.clone(), subpass_index)
+            .ok_or_else(|| RendererError::pipeline(format!("render pass has no subpass {}", subpass_index)))?;
+
+        return GraphicsPipeline::start()
+            .render_pass(subpass)
+            .vertex_input_state(vertex_input)
+            .input_assembly_state(InputAssemblyState::new())
+            .vertex_shader(vertex_entry_point, ())
+            .fragment_shader(fragment_entry_point, ())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .depth_stencil_state(DepthStencilState {
+                depth: depth_state,
+                ..DepthStencilState::disabled()
+            })
+            .multisample_state(MultisampleState {
+                rasterization_samples: self.samples,
+                ..Default::default()
+            })
+            .color_blend_state(if gbuffer {
+                ColorBlendState::new(GBUFFER_COLOR_ATTACHMENTS)
+            } else if options.transparent {
+                ColorBlendState::new(1).blend_alpha()
+            } else {
+                ColorBlendState::new(1)
+            })
+            .build(self.device.clone())
+            .map_err(RendererError::pipeline);
+    }
 
-        let command_buffer = command_buffer_builder.build().unwrap();
-        self.submit_command_buffer(command_buffer, image_acquire_future, image_index, block_until_drawn);
+    // Name of the G-buffer variant of a fragment shader, if the renderer is deferred and one is registered.
+    // Transparent materials always shade forward, since the G-buffer holds one surface per texel.
+    pub(crate) fn gbuffer_shader(&self, fragment_shader:&str, options:PipelineOptions) -> Option<String>{
+        if self.deferred_lighting.is_none() || options.transparent {
+            return None;
+        }
+        let name = format!("{}{}", fragment_shader, GBUFFER_SHADER_SUFFIX);
+        return self.shader_container.get_shader(ShaderType::Fragment, &name).map(|_| name);
     }
 
-    pub fn build_pipeline(&self, vertex_shader:Arc<ShaderModule>, fragment_shader:Arc<ShaderModule>) -> Arc<GraphicsPipeline>{
+    // Depth-only variant of a material pipeline for the shadow pass. Reads only the vertex positions, plus the instance transforms when instanced.
+    pub(crate) fn build_shadow_pipeline(&self, vertex_input:BuffersDefinition, instanced:bool) -> Result<Arc<GraphicsPipeline>, RendererError>{
+        let vertex_name: &str = if instanced { "shadow_instanced" } else { "shadow" };
+        let not_found = |shader_type:ShaderType, name:&str| RendererError::ShaderNotFound{
+            shader_type: shader_type,
+            name: String::from(name)
+        };
+        let vertex_shader = self.shader_container.get_shader(ShaderType::Vertex, vertex_name)
+            .ok_or_else(|| not_found(ShaderType::Vertex, vertex_name))?;
+        let fragment_shader = self.shader_container.get_shader(ShaderType::Fragment, "shadow")
+            .ok_or_else(|| not_found(ShaderType::Fragment, "shadow"))?;
+
+        let vertex_entry_point = vertex_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("vertex shader has no `main` entry point"))?;
+        let fragment_entry_point = fragment_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("fragment shader has no `main` entry point"))?;
+
+        let subpass = Subpass::from(self.shadow_render_pass.clone(), 0)
+            .ok_or_else(|| RendererError::pipeline("shadow render pass has no subpass 0"))?;
+
         return GraphicsPipeline::start()
-            .render_pass(Subpass::from(self.render_pass.clone(), 0).unwrap())
-            .vertex_input_state(BuffersDefinition::new().vertex::<Vertex>())
+            .render_pass(subpass)
+            .vertex_input_state(vertex_input)
             .input_assembly_state(InputAssemblyState::new())
-            .vertex_shader(vertex_shader.entry_point("main").unwrap(), ())
-            .fragment_shader(fragment_shader.entry_point("main").unwrap(), ())
+            .vertex_shader(vertex_entry_point, ())
+            .fragment_shader(fragment_entry_point, ())
             .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
-            .build(self.device.clone()).unwrap();
+            .depth_stencil_state(DepthStencilState::simple_depth_test())
+            .color_blend_state(ColorBlendState::new(0))
+            .build(self.device.clone())
+            .map_err(RendererError::pipeline);
+    }
+
+    // Labels the object in validation messages and graphics debuggers. A no-op outside debug mode.
+    pub fn set_debug_name<T: VulkanObject + DeviceOwned>(&self, object:&T, name:&str){
+        set_debug_name(&self.device, object, name);
+    }
+
+    pub fn load_shader(&mut self, shader_type:ShaderType, name:&str, path:&Path) -> Result<(), RendererError>{
+        self.shader_container.load_file(self.device.clone(), shader_type, name, path)?;
+        return Ok(());
     }
 
-    fn submit_command_buffer(&mut self, command_buffer:PrimaryAutoCommandBuffer, image_acquire_future:SwapchainAcquireFuture, image_index:u32, block_until_drawn:bool){
-        let future = self.previous_frame_end
-            .take().unwrap()
+    // While watching, every `submit_frame` recompiles changed shader files and rebuilds the materials using them.
+    pub fn watch_shaders(&mut self, watching:bool){
+        self.shader_container.set_watching(watching);
+    }
+
+    pub fn reload_changed_shaders(&mut self) -> Vec<ShaderLoadError>{
+        return self.shader_container.reload_changed(self.device.clone());
+    }
+
+    // Errors collected by reloads triggered from `submit_frame`.
+    pub fn take_shader_errors(&mut self) -> Vec<ShaderLoadError>{
+        return std::mem::take(&mut self.shader_errors);
+    }
+
+    // Records the draw calls in order, skipping binds of whatever the previous draw call already bound.
+    fn record_draw_calls(&mut self, command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, frame_index:usize, draw_calls:Vec<DrawCall>, statistics:&mut FrameStatistics) -> Result<(), RendererError>{
+        // Ids of what is currently bound, so repeated state between consecutive draw calls is not bound again.
+        let mut bound_pipeline: Option<usize> = None;
+        let mut bound_vertex_buffers: Option<(usize, Option<usize>)> = None;
+        let mut bound_index_buffer: Option<usize> = None;
+        let mut bound_frame_set: Option<usize> = None;
+        let mut bound_material_set: Option<usize> = None;
+
+        for draw_call in draw_calls {
+            statistics.draw_calls += 1;
+            let pipeline: Arc<GraphicsPipeline> = draw_call.material.pipeline();
+            let pipeline_id = Arc::as_ptr(&pipeline) as usize;
+            if bound_pipeline == Some(pipeline_id) {
+                statistics.pipeline_binds_saved += 1;
+            } else {
+                command_buffer_builder.bind_pipeline_graphics(pipeline.clone());
+                statistics.pipeline_binds += 1;
+                bound_pipeline = Some(pipeline_id);
+                // Another pipeline may use an incompatible layout, so its sets are bound again.
+                bound_frame_set = None;
+                bound_material_set = None;
+            }
+
+            let vertex_buffers_id = (
+                Arc::as_ptr(&draw_call.model.buffer) as *const () as usize,
+                draw_call.instances.as_ref().map(|instances| Arc::as_ptr(&instances.buffer) as *const () as usize));
+            if bound_vertex_buffers == Some(vertex_buffers_id) {
+                statistics.vertex_buffer_binds_saved += 1;
+            } else {
+                match &draw_call.instances {
+                    Some(instances) => {
+                        command_buffer_builder.bind_vertex_buffers(0, vec![draw_call.model.buffer.clone(), instances.buffer.clone()]);
+                    }
+                    None => {
+                        command_buffer_builder.bind_vertex_buffers(0, draw_call.model.buffer.clone());
+                    }
+                }
+                statistics.vertex_buffer_binds += 1;
+                bound_vertex_buffers = Some(vertex_buffers_id);
+            }
+            let instance_count: u32 = draw_call.instance_count();
+
+            if let Some(frame_layout) = pipeline.layout().set_layouts().get(0) {
+                if !frame_layout.bindings().is_empty() {
+                    let frame_descriptor_set = self.frames[frame_index]
+                        .descriptor_set(&self.descriptor_set_allocator, frame_layout)?;
+                    let frame_set_id = Arc::as_ptr(&frame_descriptor_set) as usize;
+                    if bound_frame_set == Some(frame_set_id) {
+                        statistics.descriptor_set_binds_saved += 1;
+                    } else {
+                        command_buffer_builder.bind_descriptor_sets(
+                            PipelineBindPoint::Graphics,
+                            pipeline.layout().clone(),
+                            0,
+                            frame_descriptor_set);
+                        statistics.descriptor_set_binds += 1;
+                        bound_frame_set = Some(frame_set_id);
+                    }
+                }
+            }
+
+            if let Some(material_descriptor_set) = draw_call.material.descriptor_set() {
+                let material_set_id = Arc::as_ptr(&material_descriptor_set) as usize;
+                if bound_material_set == Some(material_set_id) {
+                    statistics.descriptor_set_binds_saved += 1;
+                } else {
+                    command_buffer_builder.bind_descriptor_sets(
+                        PipelineBindPoint::Graphics,
+                        pipeline.layout().clone(),
+                        MATERIAL_DESCRIPTOR_SET,
+                        material_descriptor_set);
+                    statistics.descriptor_set_binds += 1;
+                    bound_material_set = Some(material_set_id);
+                }
+            }
+
+            if !pipeline.layout().push_constant_ranges().is_empty() {
+                command_buffer_builder.push_constants(
+                    pipeline.layout().clone(),
+                    0,
+                    ObjectData{
+                        transform: draw_call.transform
+                    });
+            }
+
+            if let Some(index_buffer) = &draw_call.model.indices {
+                let index_buffer_id = index_buffer.id();
+                if bound_index_buffer == Some(index_buffer_id) {
+                    statistics.index_buffer_binds_saved += 1;
+                } else {
+                    match index_buffer {
+                        IndexBuffer::U16(indices) => { command_buffer_builder.bind_index_buffer(indices.clone()); }
+                        IndexBuffer::U32(indices) => { command_buffer_builder.bind_index_buffer(indices.clone()); }
+                    }
+                    statistics.index_buffer_binds += 1;
+                    bound_index_buffer = Some(index_buffer_id);
+                }
+            }
+
+            match &draw_call.model.indices {
+                Some(indices) => {
+                    command_buffer_builder
+                        .draw_indexed(indices.len() as u32, instance_count, 0, 0, 0)
+                        .map_err(RendererError::submission)?;
+                }
+                None => {
+                    command_buffer_builder
+                        .draw(draw_call.model.vertex_count, instance_count, 0, 0)
+                        .map_err(RendererError::submission)?;
+                }
+            }
+        }
+        return Ok(());
+    }
+
+    // One full-screen triangle that shades the G-buffer written by the geometry subpass.
+    fn record_lighting_pass(&mut self, command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, frame_index:usize) -> Result<(), RendererError>{
+        let (pipeline, gbuffer_set) = match &self.deferred_lighting {
+            Some(DeferredLighting{ pipeline, gbuffer_set: Some(gbuffer_set) }) => (pipeline.clone(), gbuffer_set.clone()),
+            _ => return Ok(())
+        };
+
+        command_buffer_builder.bind_pipeline_graphics(pipeline.clone());
+        if let Some(frame_layout) = pipeline.layout().set_layouts().get(0) {
+            let frame_descriptor_set = self.frames[frame_index]
+                .descriptor_set(&self.descriptor_set_allocator, frame_layout)?;
+            command_buffer_builder.bind_descriptor_sets(
+                PipelineBindPoint::Graphics,
+                pipeline.layout().clone(),
+                0,
+                vec![frame_descriptor_set, gbuffer_set]);
+        }
+        command_buffer_builder
+            .draw(3, 1, 0, 0)
+            .map_err(RendererError::submission)?;
+        return Ok(());
+    }
+
+    // Culled against each light rather than the camera, since geometry outside the view can still cast visible shadows.
+    fn record_shadow_passes(&self, command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, shadow_map:&ShadowMap, views:&[ShadowView], draw_calls:&[DrawCall], statistics:&mut FrameStatistics) -> Result<(), RendererError>{
+        let viewport = Viewport {
+            origin: [0.0, 0.0],
+            dimensions: [shadow_map.resolution as f32, shadow_map.resolution as f32],
+            depth_range: 0.0..1.0,
+        };
+
+        for view in views {
+            command_buffer_builder
+                .begin_render_pass(
+                    RenderPassBeginInfo {
+                        clear_values: vec![Some(1f32.into())],
+                        ..RenderPassBeginInfo::framebuffer(
+                            shadow_map.layer_framebuffers[view.layer as usize].clone(),
+                        )
+                    },
+                    SubpassContents::Inline,
+                ).map_err(RendererError::submission)?
+                .set_viewport(0, [viewport.clone()]);
+
+            let frustum = Frustum::from_view_projection(&view.view_projection);
+            let mut bound_pipeline: Option<usize> = None;
+            for draw_call in draw_calls {
+                let pipeline: Arc<GraphicsPipeline> = match draw_call.material.shadow_pipeline() {
+                    Some(pipeline) => pipeline,
+                    None => continue
+                };
+                if !draw_call.is_visible(&frustum) {
+                    continue;
+                }
+
+                let pipeline_id = Arc::as_ptr(&pipeline) as usize;
+                if bound_pipeline != Some(pipeline_id) {
+                    command_buffer_builder.bind_pipeline_graphics(pipeline.clone());
+                    bound_pipeline = Some(pipeline_id);
+                }
+                command_buffer_builder.push_constants(
+                    pipeline.layout().clone(),
+                    0,
+                    ObjectData{
+                        transform: view.view_projection * draw_call.transform
+                    });
+                record_draw(command_buffer_builder, draw_call)?;
+                statistics.shadow_draw_calls += 1;
+            }
+
+            command_buffer_builder
+                .end_render_pass().map_err(RendererError::submission)?;
+        }
+        return Ok(());
+    }
+
+    // Matches the attachment order of `create_render_pass`, where the resolve target is never cleared, or of
+    // `create_deferred_render_pass`, where the G-buffer is cleared to zero.
+    fn clear_values(&self) -> Vec<Option<ClearValue>>{
+        let color: Option<ClearValue> = Some([1.0, 0.0, 0.0, 1.0].into());
+        let depth: Option<ClearValue> = Some(1f32.into());
+        if self.deferred_lighting.is_some() {
+            let empty: Option<ClearValue> = Some([0.0, 0.0, 0.0, 0.0].into());
+            return vec![color, empty, empty, empty, depth];
+        }
+        return match self.samples {
+            SampleCount::Sample1 => vec![color, depth],
+            _ => vec![color, None, depth]
+        };
+    }
+
+    fn acquire_swapchain_image(&mut self, camera:&mut Camera) -> Result<Option<(u32, SwapchainAcquireFuture)>, RendererError>{
+        let swapchain_container = match &mut self.render_target {
+            RenderTarget::Swapchain(swapchain_container) => swapchain_container,
+            RenderTarget::Offscreen(_) => return Ok(None)
+        };
+
+        let dimensions = surface_window(&swapchain_container.surface)?.inner_size();
+        if dimensions.width == 0 || dimensions.height == 0 {
+            return Ok(None);
+        }
+
+        if !swapchain_container.optimal {
+            let (new_swapchain, new_images) =
+                match swapchain_container.swapchain.recreate(SwapchainCreateInfo {
+                    image_extent: dimensions.into(),
+                    ..swapchain_container.swapchain.create_info()
+                }) {
+                    Ok(r) => r,
+                    Err(SwapchainCreationError::ImageExtentNotSupported { .. }) => return Ok(None),
+                    Err(e) => return Err(RendererError::swapchain(e)),
+                };
+
+            swapchain_container.swapchain = new_swapchain.clone();
+            swapchain_container.images = new_images.clone();
+            swapchain_container.optimal = true;
+
+            let render_path = match self.deferred_lighting {
+                Some(_) => RenderPath::Deferred,
+                None => RenderPath::Forward
+            };
+            let scene = self.post_processor.resize(&self.allocator, &new_images)?;
+            let (scene_framebuffer, gbuffer) = window_size_dependent_setup(
+                &self.allocator,
+                scene,
+                self.render_pass.clone(),
+                &mut self.viewport,
+                Some(camera),
+                self.samples,
+                render_path,
+            )?;
+            self.scene_framebuffer = scene_framebuffer;
+            if let (Some(deferred_lighting), Some(gbuffer)) = (&mut self.deferred_lighting, &gbuffer) {
+                deferred_lighting.set_gbuffer(&self.descriptor_set_allocator, gbuffer)?;
+            }
+        }
+
+        let (image_index, suboptimal, image_acquire_future) =
+            match acquire_next_image(swapchain_container.swapchain.clone(), None) {
+                Ok(r) => r,
+                Err(AcquireError::OutOfDate) => {
+                    swapchain_container.optimal = false;
+                    return Ok(None);
+                }
+                Err(e) => return Err(RendererError::swapchain(e)),
+            };
+        if suboptimal {
+            swapchain_container.optimal = false;
+        }
+
+        return Ok(Some((image_index, image_acquire_future)));
+    }
+
+    // Waits on the previous frame's fence on the GPU only, so recording the next frame can overlap with this one.
+    fn previous_frame_end(&self) -> Box<dyn GpuFuture>{
+        let previous_index = (self.frame_index + self.frames.len() - 1) % self.frames.len();
+        return match self.frames[previous_index].fence.clone() {
+            Some(fence) => fence.boxed(),
+            None => sync::now(self.device.clone()).boxed()
+        };
+    }
+
+    fn submit_command_buffer(&mut self, command_buffer:PrimaryAutoCommandBuffer, image_acquire_future:SwapchainAcquireFuture, image_index:u32, block_until_drawn:bool) -> Result<(), RendererError>{
+        let future = self.previous_frame_end()
             .join(image_acquire_future)
-            .then_execute(self.queue.clone(), command_buffer).unwrap()
+            .then_execute(self.queue.clone(), command_buffer)
+            .map_err(RendererError::submission)?;
+
+        let swapchain_container = match &mut self.render_target {
+            RenderTarget::Swapchain(swapchain_container) => swapchain_container,
+            RenderTarget::Offscreen(_) => return Err(RendererError::submission("cannot present a frame rendered to an offscreen target"))
+        };
+        let future = future
             .then_swapchain_present(
                 self.queue.clone(),
-                SwapchainPresentInfo::swapchain_image_index(self.swapchain_container.swapchain.clone(), image_index), )
+                SwapchainPresentInfo::swapchain_image_index(swapchain_container.swapchain.clone(), image_index), )
+            .boxed()
             .then_signal_fence_and_flush();
 
         match future {
             Ok(future) => {
                 if block_until_drawn {
-                    future.wait(None).unwrap();
+                    future.wait(None).map_err(RendererError::submission)?;
                 }
-                self.previous_frame_end = Some(future.boxed());
+                self.frames[self.frame_index].fence = Some(Arc::new(future));
             }
             Err(FlushError::OutOfDate) => {
-                self.swapchain_container.optimal = false;
-                self.previous_frame_end = Some(sync::now(self.device.clone()).boxed());
+                swapchain_container.optimal = false;
             }
             Err(e) => {
-                panic!("Failed to flush future: {:?}", e);
+                return Err(RendererError::submission(e));
             }
         }
+        return Ok(());
     }
+
+    // Offscreen frames always wait for the GPU so the readback buffer holds the finished frame.
+    fn submit_offscreen_command_buffer(&mut self, command_buffer:PrimaryAutoCommandBuffer) -> Result<(), RendererError>{
+        let future = self.previous_frame_end()
+            .then_execute(self.queue.clone(), command_buffer)
+            .map_err(RendererError::submission)?
+            .boxed()
+            .then_signal_fence_and_flush()
+            .map_err(RendererError::submission)?;
+
+        future.wait(None).map_err(RendererError::submission)?;
+        self.frames[self.frame_index].fence = Some(Arc::new(future));
+        return Ok(());
+    }
+}
+
+impl RenderTarget{
+    fn format(&self) -> Format{
+        return match self {
+            RenderTarget::Swapchain(swapchain_container) => swapchain_container.swapchain.image_format(),
+            RenderTarget::Offscreen(_) => OFFSCREEN_FORMAT
+        };
+    }
+}
+
+fn create_instance(
+    library: Arc<VulkanLibrary>,
+    required_extensions: InstanceExtensions,
+    config: &RendererConfig,
+) -> Result<(Arc<Instance>, Option<DebugUtilsMessenger>), RendererError> {
+    let (enabled_layers, debug_extensions) = if config.debug {
+        debug_instance_requirements(&library)
+    } else {
+        (Vec::new(), InstanceExtensions::empty())
+    };
+
+    let instance = Instance::new(
+        library,
+        InstanceCreateInfo {
+            enabled_extensions: required_extensions.union(&debug_extensions),
+            enabled_layers: enabled_layers,
+            enumerate_portability: true,
+            ..Default::default()
+        },
+    )?;
+
+    let debug_messenger = if config.debug { create_debug_messenger(&instance) } else { None };
+    return Ok((instance, debug_messenger));
+}
+
+fn select_physical_device(
+    instance: &Arc<Instance>,
+    device_extensions: &DeviceExtensions,
+    config: &RendererConfig,
+    surface: Option<&Arc<Surface>>,
+) -> Result<(Arc<PhysicalDevice>, u32), RendererError> {
+    let gpu = config.effective_gpu();
+    return instance
+        .enumerate_physical_devices().map_err(RendererError::device)?
+        .enumerate()
+        .filter(|(_, p)| {
+            p.supported_extensions().contains(device_extensions)
+                && p.supported_features().contains(&config.device_features)
+        })
+        .filter_map(|(index, p)| {
+            p.queue_family_properties()
+                .iter()
+                .enumerate()
+                .position(|(i, q)| {
+                    q.queue_flags.graphics && match surface {
+                        Some(surface) => p.surface_support(i as u32, surface).unwrap_or(false),
+                        None => true
+                    }
+                })
+                .map(|i| (index, p, i as u32))
+        })
+        // A preferred device that is unsuitable or missing falls back to the usual device-type ranking.
+        .min_by_key(|(index, p, _)| {
+            let device_type_rank = match p.properties().device_type {
+                PhysicalDeviceType::DiscreteGpu => 0,
+                PhysicalDeviceType::IntegratedGpu => 1,
+                PhysicalDeviceType::VirtualGpu => 2,
+                PhysicalDeviceType::Cpu => 3,
+                PhysicalDeviceType::Other => 4,
+                _ => 5,
+            };
+            (!gpu.matches(*index, p), device_type_rank)
+        })
+        .map(|(_, p, queue_family_index)| (p, queue_family_index))
+        .ok_or(RendererError::NoSuitableDevice);
+}
+
+fn create_device(
+    physical_device: Arc<PhysicalDevice>,
+    queue_family_index: u32,
+    device_extensions: DeviceExtensions,
+    device_features: Features,
+) -> Result<(Arc<Device>, Arc<Queue>), RendererError> {
+    let enabled_features = Features {
+        sampler_anisotropy: physical_device.supported_features().sampler_anisotropy,
+        ..Features::empty()
+    }.union(&device_features);
+
+    let (device, mut queues) = Device::new(
+        physical_device,
+        DeviceCreateInfo {
+            enabled_extensions: device_extensions,
+            enabled_features: enabled_features,
+            queue_create_infos: vec![QueueCreateInfo {
+                queue_family_index,
+                ..Default::default()
+            }],
+            ..Default::default()
+        },
+    ).map_err(RendererError::device)?;
+
+    let queue = queues.next().ok_or_else(|| RendererError::device("device was created without a queue"))?;
+    return Ok((device, queue));
+}
+
+fn choose_surface_format(surface_formats: &[(Format, ColorSpace)], config: &RendererConfig) -> Result<(Format, ColorSpace), RendererError> {
+    return config.surface_formats.iter()
+        .find(|preferred| surface_formats.contains(preferred))
+        .or_else(|| surface_formats.first())
+        .copied()
+        .ok_or_else(|| RendererError::swapchain("surface reports no supported formats"));
+}
+
+fn choose_present_mode(present_modes: &[PresentMode], config: &RendererConfig) -> PresentMode {
+    return config.present_modes.iter()
+        .copied()
+        .find(|preferred| present_modes.contains(preferred))
+        .unwrap_or(PresentMode::Fifo);
+}
+
+fn choose_image_count(surface_capabilities: &SurfaceCapabilities, config: &RendererConfig) -> u32 {
+    let min_image_count = surface_capabilities.min_image_count;
+    let max_image_count = surface_capabilities.max_image_count.unwrap_or(u32::MAX);
+    return config.image_count
+        .map_or(min_image_count, |image_count| image_count.clamp(min_image_count, max_image_count));
+}
+
+fn choose_composite_alpha(surface_capabilities: &SurfaceCapabilities, config: &RendererConfig) -> Result<CompositeAlpha, RendererError> {
+    let supported = surface_capabilities.supported_composite_alpha;
+    return config.composite_alpha
+        .filter(|preferred| supported.iter().any(|composite_alpha| composite_alpha == *preferred))
+        .or_else(|| supported.iter().next())
+        .ok_or_else(|| RendererError::swapchain("surface supports no composite alpha mode"));
+}
+
+// Binds the draw call's buffers and draws it, without checking what is already bound.
+fn record_draw(command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, draw_call:&DrawCall) -> Result<(), RendererError>{
+    match &draw_call.instances {
+        Some(instances) => {
+            command_buffer_builder.bind_vertex_buffers(0, vec![draw_call.model.buffer.clone(), instances.buffer.clone()]);
+        }
+        None => {
+            command_buffer_builder.bind_vertex_buffers(0, draw_call.model.buffer.clone());
+        }
+    }
+
+    match &draw_call.model.indices {
+        Some(indices) => {
+            match indices {
+                IndexBuffer::U16(indices) => { command_buffer_builder.bind_index_buffer(indices.clone()); }
+                IndexBuffer::U32(indices) => { command_buffer_builder.bind_index_buffer(indices.clone()); }
+            }
+            command_buffer_builder
+                .draw_indexed(indices.len() as u32, draw_call.instance_count(), 0, 0, 0)
+                .map_err(RendererError::submission)?;
+        }
+        None => {
+            command_buffer_builder
+                .draw(draw_call.model.vertex_count, draw_call.instance_count(), 0, 0)
+                .map_err(RendererError::submission)?;
+        }
+    }
+    return Ok(());
+}
+
+fn create_render_pass(device: Arc<Device>, format: Format, samples: SampleCount) -> Result<Arc<RenderPass>, RendererError> {
+    if samples == SampleCount::Sample1 {
+        return vulkano::single_pass_renderpass!(
+            device,
+            attachments: {
+                color: {
+                    load: Clear,
+                    store: Store,
+                    format: format,
+                    samples: 1,
+                },
+                depth: {
+                    load: Clear,
+                    store: DontCare,
+                    format: DEPTH_FORMAT,
+                    samples: 1,
+                }
+            },
+            pass: {
+                color: [color],
+                depth_stencil: {depth}
+            }).map_err(RendererError::pipeline);
+    }
+
+    // Rendering happens in the transient multisampled attachment, which is resolved into the target at the end of the pass.
+    return vulkano::single_pass_renderpass!(
+        device,
+        attachments: {
+            multisampled: {
+                load: Clear,
+                store: DontCare,
+                format: format,
+                samples: samples as u32,
+            },
+            color: {
+                load: DontCare,
+                store: Store,
+                format: format,
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: DEPTH_FORMAT,
+                samples: samples as u32,
+            }
+        },
+        pass: {
+            color: [multisampled],
+            depth_stencil: {depth},
+            resolve: [color],
+        }).map_err(RendererError::pipeline);
+}
+
+// Falls back to the highest count below the requested one that both color and depth attachments support.
+fn clamp_sample_count(physical_device: &PhysicalDevice, requested: SampleCount) -> SampleCount {
+    let properties = physical_device.properties();
+    let supported = properties.framebuffer_color_sample_counts
+        .intersection(&properties.framebuffer_depth_sample_counts);
+    return [
+        SampleCount::Sample64,
+        SampleCount::Sample32,
+        SampleCount::Sample16,
+        SampleCount::Sample8,
+        SampleCount::Sample4,
+        SampleCount::Sample2,
+    ]
+        .into_iter()
+        .filter(|samples| (*samples as u32) <= (requested as u32))
+        .find(|samples| match samples {
+            SampleCount::Sample64 => supported.sample64,
+            SampleCount::Sample32 => supported.sample32,
+            SampleCount::Sample16 => supported.sample16,
+            SampleCount::Sample8 => supported.sample8,
+            SampleCount::Sample4 => supported.sample4,
+            SampleCount::Sample2 => supported.sample2,
+            _ => false
+        })
+        .unwrap_or(SampleCount::Sample1);
+}
+
+fn surface_window(surface: &Arc<Surface>) -> Result<&Window, RendererError> {
+    return surface.object()
+        .and_then(|object| object.downcast_ref::<Window>())
+        .ok_or_else(|| RendererError::swapchain("surface is not backed by a winit window"));
 }
 
 fn window_size_dependent_setup(
-    images: &[Arc<SwapchainImage>],
+    allocator: &StandardMemoryAllocator,
+    scene: Arc<ImageView<AttachmentImage>>,
     render_pass: Arc<RenderPass>,
     viewport: &mut Viewport,
-) -> Vec<Arc<Framebuffer>> {
-    let dimensions = images[0].dimensions().width_height();
+    camera: Option<&mut Camera>,
+    samples: SampleCount,
+    render_path: RenderPath,
+) -> Result<(Arc<Framebuffer>, Option<GBuffer>), RendererError> {
+    let dimensions = scene.image().dimensions().width_height();
     viewport.dimensions = [dimensions[0] as f32, dimensions[1] as f32];
+    if let Some(camera) = camera {
+        camera.set_aspect_ratio(viewport.dimensions[0] / viewport.dimensions[1]);
+    }
 
-    images
-        .iter()
-        .map(|image| {
-            let view = ImageView::new_default(image.clone()).unwrap();
-            Framebuffer::new(
-                render_pass.clone(),
-                FramebufferCreateInfo {
-                    attachments: vec![view],
-                    ..Default::default()
-                },
-            )
-                .unwrap()
-        })
-        .collect::<Vec<_>>()
-}
\ No newline at end of file
+    if render_path == RenderPath::Deferred {
+        let gbuffer = GBuffer::new(allocator, dimensions, DEPTH_FORMAT)?;
+        let framebuffer = Framebuffer::new(
+            render_pass,
+            FramebufferCreateInfo {
+                attachments: vec![
+                    scene,
+                    gbuffer.albedo.clone(),
+                    gbuffer.normal.clone(),
+                    gbuffer.material.clone(),
+                    gbuffer.depth.clone()
+                ],
+                ..Default::default()
+            },
+        ).map_err(RendererError::allocation)?;
+        return Ok((framebuffer, Some(gbuffer)));
+    }
+
+    let depth_image = AttachmentImage::transient_multisampled(allocator, dimensions, samples, DEPTH_FORMAT)
+        .map_err(RendererError::allocation)?;
+    set_debug_name(allocator.device(), depth_image.inner().image.as_ref(), "depth buffer");
+    let depth_buffer = ImageView::new_default(depth_image).map_err(RendererError::allocation)?;
+
+    let attachments: Vec<Arc<dyn ImageViewAbstract>> = if samples == SampleCount::Sample1 {
+        vec![scene, depth_buffer]
+    } else {
+        let multisampled_image = AttachmentImage::transient_multisampled(allocator, dimensions, samples, scene.image().format())
+            .map_err(RendererError::allocation)?;
+        set_debug_name(allocator.device(), multisampled_image.inner().image.as_ref(), "multisampled color buffer");
+        let multisampled_buffer = ImageView::new_default(multisampled_image).map_err(RendererError::allocation)?;
+        vec![multisampled_buffer, scene, depth_buffer]
+    };
+
+    let framebuffer = Framebuffer::new(
+        render_pass,
+        FramebufferCreateInfo {
+            attachments: attachments,
+            ..Default::default()
+        },
+    ).map_err(RendererError::allocation)?;
+    return Ok((framebuffer, None));
+}
@@ -0,0 +1,204 @@
+use nalgebra_glm as glm;
+use nalgebra_glm::{Mat4x4, Vec3, Vec4};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb{
+    pub min: Vec3,
+    pub max: Vec3
+}
+
+impl Aabb {
+    // An empty point set gives a degenerate box at the origin.
+    pub fn from_points(points:impl IntoIterator<Item = [f32; 3]>) -> Aabb{
+        let mut points = points.into_iter().map(Vec3::from);
+        let first: Vec3 = match points.next() {
+            Some(first) => first,
+            None => return Aabb{ min: Vec3::zeros(), max: Vec3::zeros() }
+        };
+        return points.fold(Aabb{ min: first, max: first }, |aabb, point| Aabb{
+            min: glm::min2(&aabb.min, &point),
+            max: glm::max2(&aabb.max, &point)
+        });
+    }
+
+    pub fn center(&self) -> Vec3{
+        return (self.min + self.max) * 0.5;
+    }
+
+    pub fn extents(&self) -> Vec3{
+        return (self.max - self.min) * 0.5;
+    }
+
+    // The box enclosing this one after it is transformed, so it may be larger than the tightest fit.
+    pub fn transformed(&self, transform:&Mat4x4) -> Aabb{
+        let center: Vec3 = (transform * self.center().push(1.0)).xyz();
+        let extents: Vec3 = self.extents();
+        let basis = transform.fixed_slice::<3, 3>(0, 0);
+        let world_extents: Vec3 = basis.abs() * extents;
+        return Aabb{
+            min: center - world_extents,
+            max: center + world_extents
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere{
+    pub center: Vec3,
+    pub radius: f32
+}
+
+impl BoundingSphere {
+    // Centered on the box, with the radius reaching the farthest point rather than the box corner.
+    pub fn from_points(aabb:&Aabb, points:impl IntoIterator<Item = [f32; 3]>) -> BoundingSphere{
+        let center: Vec3 = aabb.center();
+        let radius: f32 = points.into_iter()
+            .map(|point| glm::distance2(&Vec3::from(point), &center))
+            .fold(0.0, f32::max)
+            .sqrt();
+        return BoundingSphere{
+            center: center,
+            radius: radius
+        };
+    }
+
+    // Non-uniform scale grows the radius by the largest axis scale.
+    pub fn transformed(&self, transform:&Mat4x4) -> BoundingSphere{
+        let center: Vec3 = (transform * self.center.push(1.0)).xyz();
+        let scale: f32 = (0..3)
+            .map(|column| transform.fixed_slice::<3, 1>(0, column).norm())
+            .fold(0.0, f32::max);
+        return BoundingSphere{
+            center: center,
+            radius: self.radius * scale
+        };
+    }
+}
+
+// Planes point inwards, a point is inside when `dot(plane.xyz, point) + plane.w >= 0` for all six.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Frustum{
+    pub planes: [Vec4; 6]
+}
+
+impl Frustum {
+    // Expects a Vulkan style projection with depth in 0..1.
+    pub fn from_view_projection(view_projection:&Mat4x4) -> Frustum{
+        let row = |i:usize| -> Vec4 { view_projection.row(i).transpose() };
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(2),
+            row(3) - row(2)
+        ];
+        return Frustum{
+            planes: planes.map(|plane| plane / plane.xyz().norm())
+        };
+    }
+
+    pub fn intersects_sphere(&self, sphere:&BoundingSphere) -> bool{
+        return self.planes.iter()
+            .all(|plane| glm::dot(&plane.xyz(), &sphere.center) + plane.w >= -sphere.radius);
+    }
+
+    pub fn intersects_aabb(&self, aabb:&Aabb) -> bool{
+        let center: Vec3 = aabb.center();
+        let extents: Vec3 = aabb.extents();
+        return self.planes.iter().all(|plane| {
+            let normal: Vec3 = plane.xyz();
+            let reach: f32 = glm::dot(&normal.abs(), &extents);
+            glm::dot(&normal, &center) + plane.w >= -reach
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera_frustum() -> Frustum{
+        // At the origin looking down -Z, near 0.1 and far 100.
+        let projection: Mat4x4 = glm::perspective_rh_zo(1.0, glm::half_pi(), 0.1, 100.0);
+        let view: Mat4x4 = glm::look_at_rh(&Vec3::zeros(), &Vec3::new(0.0, 0.0, -1.0), &Vec3::y());
+        return Frustum::from_view_projection(&(projection * view));
+    }
+
+    fn unit_box_at(center:Vec3) -> Aabb{
+        return Aabb{ min: center - Vec3::new(0.5, 0.5, 0.5), max: center + Vec3::new(0.5, 0.5, 0.5) };
+    }
+
+    #[test]
+    fn aabb_from_points_spans_all_points(){
+        let aabb = Aabb::from_points([[1.0, -2.0, 0.0], [-1.0, 3.0, 0.5], [0.0, 0.0, -4.0]]);
+        assert_eq!(aabb.min, Vec3::new(-1.0, -2.0, -4.0));
+        assert_eq!(aabb.max, Vec3::new(1.0, 3.0, 0.5));
+    }
+
+    #[test]
+    fn aabb_from_no_points_is_degenerate(){
+        let aabb = Aabb::from_points(std::iter::empty());
+        assert_eq!(aabb.min, Vec3::zeros());
+        assert_eq!(aabb.max, Vec3::zeros());
+    }
+
+    #[test]
+    fn aabb_transformed_by_rotation_swaps_extents(){
+        let aabb = Aabb{ min: Vec3::new(-1.0, -2.0, -3.0), max: Vec3::new(1.0, 2.0, 3.0) };
+        let transform: Mat4x4 = glm::translation(&Vec3::new(10.0, 0.0, 0.0))
+            * glm::rotation(glm::half_pi(), &Vec3::z());
+        let transformed = aabb.transformed(&transform);
+        assert!(glm::distance(&transformed.center(), &Vec3::new(10.0, 0.0, 0.0)) < 1e-5);
+        assert!(glm::distance(&transformed.extents(), &Vec3::new(2.0, 1.0, 3.0)) < 1e-5);
+    }
+
+    #[test]
+    fn sphere_reaches_farthest_point(){
+        let points = [[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [1.0, 3.0, 0.0]];
+        let aabb = Aabb::from_points(points);
+        let sphere = BoundingSphere::from_points(&aabb, points);
+        assert_eq!(sphere.center, Vec3::new(1.0, 1.5, 0.0));
+        // The corners at y = 0 are farther from the center than the point at the top.
+        assert!((sphere.radius - 3.25f32.sqrt()).abs() < 1e-5);
+    }
+
+    #[test]
+    fn sphere_transformed_scales_by_largest_axis(){
+        let sphere = BoundingSphere{ center: Vec3::new(1.0, 0.0, 0.0), radius: 1.0 };
+        let transform: Mat4x4 = glm::scaling(&Vec3::new(2.0, 3.0, 1.0));
+        let transformed = sphere.transformed(&transform);
+        assert_eq!(transformed.center, Vec3::new(2.0, 0.0, 0.0));
+        assert!((transformed.radius - 3.0).abs() < 1e-5);
+    }
+
+    #[test]
+    fn frustum_contains_sphere_in_front(){
+        let frustum = camera_frustum();
+        assert!(frustum.intersects_sphere(&BoundingSphere{ center: Vec3::new(0.0, 0.0, -5.0), radius: 1.0 }));
+    }
+
+    #[test]
+    fn frustum_rejects_sphere_behind_and_beyond_far(){
+        let frustum = camera_frustum();
+        assert!(!frustum.intersects_sphere(&BoundingSphere{ center: Vec3::new(0.0, 0.0, 5.0), radius: 1.0 }));
+        assert!(!frustum.intersects_sphere(&BoundingSphere{ center: Vec3::new(0.0, 0.0, -200.0), radius: 1.0 }));
+    }
+
+    #[test]
+    fn frustum_keeps_sphere_straddling_a_side_plane(){
+        let frustum = camera_frustum();
+        // The 90 degree frustum's right plane passes through x = 5 at z = -5.
+        assert!(frustum.intersects_sphere(&BoundingSphere{ center: Vec3::new(5.5, 0.0, -5.0), radius: 1.0 }));
+        assert!(!frustum.intersects_sphere(&BoundingSphere{ center: Vec3::new(8.0, 0.0, -5.0), radius: 1.0 }));
+    }
+
+    #[test]
+    fn frustum_tests_aabbs(){
+        let frustum = camera_frustum();
+        assert!(frustum.intersects_aabb(&unit_box_at(Vec3::new(0.0, 0.0, -5.0))));
+        assert!(frustum.intersects_aabb(&unit_box_at(Vec3::new(5.3, 0.0, -5.0))));
+        assert!(!frustum.intersects_aabb(&unit_box_at(Vec3::new(0.0, 0.0, 5.0))));
+        assert!(!frustum.intersects_aabb(&unit_box_at(Vec3::new(0.0, -10.0, -5.0))));
+    }
+}
@@ -0,0 +1,208 @@
+use std::env;
+use vulkano::device::physical::PhysicalDevice;
+use vulkano::device::{DeviceExtensions, Features};
+use vulkano::format::Format;
+use vulkano::image::SampleCount;
+use vulkano::swapchain::{ColorSpace, CompositeAlpha, PresentMode};
+use crate::renderer::post_process::Exposure;
+use crate::renderer::shadow::ShadowSettings;
+
+// Overrides `RendererConfig::gpu`. A plain number selects by index, `0x`-prefixed hex by vendor id, anything else by name.
+pub const GPU_ENV_VAR: &str = "VULKANUS_GPU";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GpuPreference{
+    // Discrete, then integrated, virtual and CPU devices.
+    Any,
+    // Case-insensitive substring of the device name.
+    Name(String),
+    // PCI vendor id, e.g. 0x10DE for NVIDIA.
+    Vendor(u32),
+    // Position in the instance's device enumeration.
+    Index(usize)
+}
+
+impl GpuPreference {
+    pub fn from_env() -> Option<GpuPreference>{
+        let value = env::var(GPU_ENV_VAR).ok()?;
+        let value = value.trim();
+        if value.is_empty() {
+            return None;
+        }
+        if let Some(hex) = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+            if let Ok(vendor) = u32::from_str_radix(hex, 16) {
+                return Some(GpuPreference::Vendor(vendor));
+            }
+        }
+        return match value.parse::<usize>() {
+            Ok(index) => Some(GpuPreference::Index(index)),
+            Err(_) => Some(GpuPreference::Name(String::from(value)))
+        };
+    }
+
+    pub(crate) fn matches(&self, index:usize, physical_device:&PhysicalDevice) -> bool{
+        let properties = physical_device.properties();
+        return match self {
+            GpuPreference::Any => true,
+            GpuPreference::Name(name) => properties.device_name.to_lowercase().contains(&name.to_lowercase()),
+            GpuPreference::Vendor(vendor) => properties.vendor_id == *vendor,
+            GpuPreference::Index(preferred) => index == *preferred
+        };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderPath{
+    // Every draw call is shaded as it is drawn.
+    Forward,
+    // Lit materials write a G-buffer that one full-screen pass shades, so the cost of lighting no longer scales with
+    // overdraw. Transparent and unlit materials are still drawn forward afterwards. Disables MSAA.
+    Deferred
+}
+
+#[derive(Clone, Debug)]
+pub struct RendererConfig{
+    pub gpu: GpuPreference,
+    // Tried in order; FIFO is always supported and is used when none of them are.
+    pub present_modes: Vec<PresentMode>,
+    // Clamped to what the surface supports. `None` uses the surface minimum.
+    pub image_count: Option<u32>,
+    // Tried in order; the first format the surface reports is used when none of them are supported.
+    pub surface_formats: Vec<(Format, ColorSpace)>,
+    pub composite_alpha: Option<CompositeAlpha>,
+    pub device_extensions: DeviceExtensions,
+    pub device_features: Features,
+    // How many frames the CPU may record ahead of the GPU. Each one gets its own fence, uniform buffer and descriptor sets.
+    pub frames_in_flight: usize,
+    // Skips draw calls whose bounds lie outside the camera frustum. Off by default.
+    pub frustum_culling: bool,
+    // Orders opaque draw calls front-to-back and transparent ones back-to-front. Without it only state is sorted on.
+    pub sort_by_depth: bool,
+    // MSAA sample count, lowered to the highest count the device supports for both color and depth.
+    pub samples: SampleCount,
+    pub render_path: RenderPath,
+    // Shadow map size and cascades. Fixed once the renderer is created, since the shadow maps are allocated up front.
+    pub shadows: ShadowSettings,
+    // Renders the scene in a floating-point format so lighting can exceed 1.0, tone mapping it into the target at the end.
+    // Off by default, since tone mapping changes the colors of existing scenes.
+    pub hdr: bool,
+    // Applied by tone mapping, can be changed later with `Renderer::set_exposure`.
+    pub exposure: Exposure,
+    // Enables the Khronos validation layer, routes Vulkan messages to `log` and names GPU objects.
+    pub debug: bool
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self{
+        return Self{
+            gpu: GpuPreference::Any,
+            present_modes: vec![PresentMode::Fifo],
+            image_count: None,
+            surface_formats: Vec::new(),
+            composite_alpha: None,
+            device_extensions: DeviceExtensions::empty(),
+            device_features: Features::empty(),
+            frames_in_flight: 2,
+            frustum_culling: false,
+            sort_by_depth: true,
+            samples: SampleCount::Sample1,
+            render_path: RenderPath::Forward,
+            shadows: ShadowSettings::default(),
+            hdr: false,
+            exposure: Exposure::default(),
+            debug: false
+        };
+    }
+}
+
+impl RendererConfig {
+    pub fn gpu(mut self, gpu:GpuPreference) -> Self{
+        self.gpu = gpu;
+        return self;
+    }
+
+    pub fn present_mode(mut self, present_mode:PresentMode) -> Self{
+        self.present_modes = vec![present_mode];
+        return self;
+    }
+
+    pub fn present_modes(mut self, present_modes:Vec<PresentMode>) -> Self{
+        self.present_modes = present_modes;
+        return self;
+    }
+
+    pub fn image_count(mut self, image_count:u32) -> Self{
+        self.image_count = Some(image_count);
+        return self;
+    }
+
+    pub fn surface_format(mut self, format:Format, color_space:ColorSpace) -> Self{
+        self.surface_formats.push((format, color_space));
+        return self;
+    }
+
+    pub fn composite_alpha(mut self, composite_alpha:CompositeAlpha) -> Self{
+        self.composite_alpha = Some(composite_alpha);
+        return self;
+    }
+
+    pub fn device_extensions(mut self, device_extensions:DeviceExtensions) -> Self{
+        self.device_extensions = self.device_extensions.union(&device_extensions);
+        return self;
+    }
+
+    pub fn device_features(mut self, device_features:Features) -> Self{
+        self.device_features = self.device_features.union(&device_features);
+        return self;
+    }
+
+    pub fn frames_in_flight(mut self, frames_in_flight:usize) -> Self{
+        self.frames_in_flight = frames_in_flight.max(1);
+        return self;
+    }
+
+    pub fn frustum_culling(mut self, frustum_culling:bool) -> Self{
+        self.frustum_culling = frustum_culling;
+        return self;
+    }
+
+    pub fn sort_by_depth(mut self, sort_by_depth:bool) -> Self{
+        self.sort_by_depth = sort_by_depth;
+        return self;
+    }
+
+    pub fn samples(mut self, samples:SampleCount) -> Self{
+        self.samples = samples;
+        return self;
+    }
+
+    pub fn render_path(mut self, render_path:RenderPath) -> Self{
+        self.render_path = render_path;
+        return self;
+    }
+
+    pub fn shadows(mut self, shadows:ShadowSettings) -> Self{
+        self.shadows = shadows;
+        return self;
+    }
+
+    pub fn hdr(mut self, hdr:bool) -> Self{
+        self.hdr = hdr;
+        return self;
+    }
+
+    pub fn exposure(mut self, exposure:Exposure) -> Self{
+        self.exposure = exposure;
+        return self;
+    }
+
+    pub fn debug(mut self, debug:bool) -> Self{
+        self.debug = debug;
+        return self;
+    }
+
+    // The environment variable wins over whatever was configured in code.
+    pub(crate) fn effective_gpu(&self) -> GpuPreference{
+        return GpuPreference::from_env().unwrap_or_else(|| self.gpu.clone());
+    }
+}
@@ -0,0 +1,99 @@
+use std::sync::Arc;
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::instance::debug::{
+    DebugUtilsMessageSeverity, DebugUtilsMessageType, DebugUtilsMessenger, DebugUtilsMessengerCreateInfo, Message,
+};
+use vulkano::instance::{Instance, InstanceExtensions};
+use vulkano::{VulkanLibrary, VulkanObject};
+
+pub const VALIDATION_LAYER: &str = "VK_LAYER_KHRONOS_validation";
+
+const LOG_TARGET: &str = "vulkan";
+
+// Layers and instance extensions to enable on top of the required ones. Missing pieces are logged and skipped.
+pub(crate) fn debug_instance_requirements(library:&VulkanLibrary) -> (Vec<String>, InstanceExtensions){
+    let mut layers: Vec<String> = Vec::new();
+    let has_validation_layer = library.layer_properties()
+        .map_or(false, |mut properties| properties.any(|layer| layer.name() == VALIDATION_LAYER));
+    if has_validation_layer {
+        layers.push(String::from(VALIDATION_LAYER));
+    } else {
+        log::warn!(target: LOG_TARGET, "{} is not installed, validation is disabled", VALIDATION_LAYER);
+    }
+
+    let mut extensions = InstanceExtensions::empty();
+    if library.supported_extensions().ext_debug_utils {
+        extensions.ext_debug_utils = true;
+    } else {
+        log::warn!(target: LOG_TARGET, "VK_EXT_debug_utils is not supported, debug messages and object names are disabled");
+    }
+    return (layers, extensions);
+}
+
+pub(crate) fn create_debug_messenger(instance:&Arc<Instance>) -> Option<DebugUtilsMessenger>{
+    if !instance.enabled_extensions().ext_debug_utils {
+        return None;
+    }
+
+    let messenger = unsafe {
+        DebugUtilsMessenger::new(
+            instance.clone(),
+            DebugUtilsMessengerCreateInfo {
+                message_severity: DebugUtilsMessageSeverity {
+                    error: true,
+                    warning: true,
+                    information: true,
+                    verbose: true,
+                    ..DebugUtilsMessageSeverity::empty()
+                },
+                message_type: DebugUtilsMessageType {
+                    general: true,
+                    validation: true,
+                    performance: true,
+                    ..DebugUtilsMessageType::empty()
+                },
+                ..DebugUtilsMessengerCreateInfo::user_callback(Arc::new(log_message))
+            },
+        )
+    };
+
+    return match messenger {
+        Ok(messenger) => Some(messenger),
+        Err(error) => {
+            log::warn!(target: LOG_TARGET, "failed to install the debug messenger: {}", error);
+            None
+        }
+    };
+}
+
+fn log_message(message:&Message){
+    let level = if message.severity.error {
+        log::Level::Error
+    } else if message.severity.warning {
+        log::Level::Warn
+    } else if message.severity.information {
+        log::Level::Info
+    } else {
+        log::Level::Trace
+    };
+
+    let kind = if message.ty.validation {
+        "validation"
+    } else if message.ty.performance {
+        "performance"
+    } else {
+        "general"
+    };
+
+    log::log!(target: LOG_TARGET, level, "[{}] {}: {}", kind, message.layer_prefix.unwrap_or("vulkan"), message.description);
+}
+
+// A no-op unless the instance was created in debug mode.
+pub(crate) fn set_debug_name<T: VulkanObject + DeviceOwned>(device:&Arc<Device>, object:&T, name:&str){
+    if !device.instance().enabled_extensions().ext_debug_utils {
+        return;
+    }
+    if let Err(error) = device.set_debug_utils_object_name(object, Some(name)) {
+        log::warn!(target: LOG_TARGET, "failed to name {}: {}", name, error);
+    }
+}
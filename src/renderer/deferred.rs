@@ -0,0 +1,185 @@
+use std::sync::Arc;
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage, ImageAccess};
+use vulkano::image::view::ImageView;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::ViewportState;
+use vulkano::pipeline::{GraphicsPipeline, Pipeline};
+use vulkano::render_pass::{RenderPass, Subpass};
+use crate::renderer::debug::set_debug_name;
+use crate::renderer::error::RendererError;
+use crate::renderer::shader_loader::ShaderContainer;
+use crate::renderer::ShaderType;
+
+// Subpasses of the deferred render pass. Transparent and unlit materials are drawn forward on top of the lit image.
+pub const GEOMETRY_SUBPASS: u32 = 0;
+pub const LIGHTING_SUBPASS: u32 = 1;
+pub const FORWARD_SUBPASS: u32 = 2;
+
+// A material whose fragment shader `name` has a `name_gbuffer` variant registered writes the G-buffer instead of shading forward.
+pub const GBUFFER_SHADER_SUFFIX: &str = "_gbuffer";
+
+// Emissive color followed by albedo, normal and material parameters, in the order the G-buffer shaders write them.
+pub(crate) const GBUFFER_COLOR_ATTACHMENTS: u32 = 4;
+
+// The lighting pass reads the G-buffer as input attachments from this set.
+const GBUFFER_DESCRIPTOR_SET: usize = 1;
+
+const ALBEDO_FORMAT: Format = Format::R8G8B8A8_UNORM;
+const NORMAL_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+const MATERIAL_FORMAT: Format = Format::R8G8B8A8_UNORM;
+
+// Lives for one window size, recreated together with the framebuffers.
+pub(crate) struct GBuffer{
+    // Albedo in rgb, occlusion in a.
+    pub(crate) albedo: Arc<ImageView<AttachmentImage>>,
+    // World space normal in rgb.
+    pub(crate) normal: Arc<ImageView<AttachmentImage>>,
+    // Metallic or specular, roughness or shininess, unused, lighting model.
+    pub(crate) material: Arc<ImageView<AttachmentImage>>,
+    pub(crate) depth: Arc<ImageView<AttachmentImage>>
+}
+
+impl GBuffer {
+    pub(crate) fn new(allocator:&StandardMemoryAllocator, dimensions:[u32; 2], depth_format:Format) -> Result<Self, RendererError>{
+        let attachment = |format:Format, name:&str| -> Result<Arc<ImageView<AttachmentImage>>, RendererError> {
+            let image = AttachmentImage::transient_input_attachment(allocator, dimensions, format)
+                .map_err(RendererError::allocation)?;
+            set_debug_name(allocator.device(), image.inner().image.as_ref(), name);
+            return ImageView::new_default(image).map_err(RendererError::allocation);
+        };
+
+        return Ok(Self{
+            albedo: attachment(ALBEDO_FORMAT, "g-buffer albedo")?,
+            normal: attachment(NORMAL_FORMAT, "g-buffer normal")?,
+            material: attachment(MATERIAL_FORMAT, "g-buffer material")?,
+            depth: attachment(depth_format, "g-buffer depth")?
+        });
+    }
+}
+
+// The full-screen pass that shades every G-buffer texel with the frame's lights and shadows.
+pub(crate) struct DeferredLighting{
+    pub(crate) pipeline: Arc<GraphicsPipeline>,
+    // Points at the current G-buffer, `None` until the first one is created.
+    pub(crate) gbuffer_set: Option<Arc<PersistentDescriptorSet>>
+}
+
+impl DeferredLighting {
+    pub(crate) fn new(device:Arc<Device>, shader_container:&ShaderContainer, render_pass:Arc<RenderPass>) -> Result<Self, RendererError>{
+        let not_found = |shader_type:ShaderType, name:&str| RendererError::ShaderNotFound{
+            shader_type: shader_type,
+            name: String::from(name)
+        };
+        let vertex_shader = shader_container.get_shader(ShaderType::Vertex, "fullscreen")
+            .ok_or_else(|| not_found(ShaderType::Vertex, "fullscreen"))?;
+        let fragment_shader = shader_container.get_shader(ShaderType::Fragment, "deferred_lighting")
+            .ok_or_else(|| not_found(ShaderType::Fragment, "deferred_lighting"))?;
+
+        let vertex_entry_point = vertex_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("vertex shader has no `main` entry point"))?;
+        let fragment_entry_point = fragment_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("fragment shader has no `main` entry point"))?;
+
+        let subpass = Subpass::from(render_pass, LIGHTING_SUBPASS)
+            .ok_or_else(|| RendererError::pipeline("deferred render pass has no lighting subpass"))?;
+
+        // Lighting is added on top of the emissive color the geometry subpass left in the target.
+        let pipeline = GraphicsPipeline::start()
+            .render_pass(subpass)
+            .vertex_input_state(BuffersDefinition::new())
+            .input_assembly_state(InputAssemblyState::new())
+            .vertex_shader(vertex_entry_point, ())
+            .fragment_shader(fragment_entry_point, ())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .color_blend_state(ColorBlendState::new(1).blend_additive())
+            .build(device.clone())
+            .map_err(RendererError::pipeline)?;
+        set_debug_name(&device, pipeline.as_ref(), "deferred lighting pipeline");
+
+        return Ok(Self{
+            pipeline: pipeline,
+            gbuffer_set: None
+        });
+    }
+
+    pub(crate) fn set_gbuffer(&mut self, descriptor_set_allocator:&StandardDescriptorSetAllocator, gbuffer:&GBuffer) -> Result<(), RendererError>{
+        let layout = self.pipeline.layout().set_layouts()
+            .get(GBUFFER_DESCRIPTOR_SET)
+            .ok_or_else(|| RendererError::pipeline("lighting shader declares no g-buffer inputs in set 1"))?
+            .clone();
+
+        self.gbuffer_set = Some(PersistentDescriptorSet::new(
+            descriptor_set_allocator,
+            layout,
+            [
+                WriteDescriptorSet::image_view(0, gbuffer.albedo.clone()),
+                WriteDescriptorSet::image_view(1, gbuffer.normal.clone()),
+                WriteDescriptorSet::image_view(2, gbuffer.material.clone()),
+                WriteDescriptorSet::image_view(3, gbuffer.depth.clone())
+            ],
+        ).map_err(RendererError::allocation)?);
+        return Ok(());
+    }
+}
+
+pub(crate) fn create_deferred_render_pass(device:Arc<Device>, format:Format, depth_format:Format) -> Result<Arc<RenderPass>, RendererError>{
+    return vulkano::ordered_passes_renderpass!(
+        device,
+        attachments: {
+            color: {
+                load: Clear,
+                store: Store,
+                format: format,
+                samples: 1,
+            },
+            albedo: {
+                load: Clear,
+                store: DontCare,
+                format: ALBEDO_FORMAT,
+                samples: 1,
+            },
+            normal: {
+                load: Clear,
+                store: DontCare,
+                format: NORMAL_FORMAT,
+                samples: 1,
+            },
+            material: {
+                load: Clear,
+                store: DontCare,
+                format: MATERIAL_FORMAT,
+                samples: 1,
+            },
+            depth: {
+                load: Clear,
+                store: DontCare,
+                format: depth_format,
+                samples: 1,
+            }
+        },
+        passes: [
+            {
+                color: [color, albedo, normal, material],
+                depth_stencil: {depth},
+                input: []
+            },
+            {
+                color: [color],
+                depth_stencil: {},
+                input: [albedo, normal, material, depth]
+            },
+            {
+                color: [color],
+                depth_stencil: {depth},
+                input: []
+            }
+        ]
+    ).map_err(RendererError::pipeline);
+}
@@ -1,9 +1,57 @@
 use nalgebra_glm::Mat4x4;
 use crate::material::Material;
+use crate::renderer::bounds::Frustum;
+use crate::renderer::instance::InstanceBuffer;
 use crate::renderer::model::Model;
 
 pub struct DrawCall{
     pub transform:Mat4x4,
     pub model:Model,
-    pub material:Material
-}
\ No newline at end of file
+    pub material:Material,
+    // Draw calls are sorted by layer first, so a higher layer is always drawn after a lower one.
+    pub layer:u8,
+    // When set, the model is drawn once per instance and `transform` applies to all of them.
+    pub instances:Option<InstanceBuffer>
+}
+
+impl DrawCall {
+    pub fn new(transform:Mat4x4, model:Model, material:Material) -> Self{
+        return Self{
+            transform: transform,
+            model: model,
+            material: material,
+            layer: 0,
+            instances: None
+        };
+    }
+
+    // The material has to be built for the instance format, see `Material::for_instanced_format`.
+    pub fn instanced(model:Model, material:Material, instances:InstanceBuffer) -> Self{
+        return Self{
+            transform: Mat4x4::identity(),
+            model: model,
+            material: material,
+            layer: 0,
+            instances: Some(instances)
+        };
+    }
+
+    pub fn with_layer(mut self, layer:u8) -> Self{
+        self.layer = layer;
+        return self;
+    }
+
+    // Instanced draw calls are never culled, their instances can be spread anywhere around the model. Neither are
+    // materials that ignore the camera, like `direct`, whose vertices are not placed by the view-projection.
+    pub(crate) fn is_visible(&self, frustum:&Frustum) -> bool{
+        if self.instances.is_some() || !self.material.uses_camera() {
+            return true;
+        }
+        return frustum.intersects_sphere(&self.model.bounding_sphere.transformed(&self.transform))
+            && frustum.intersects_aabb(&self.model.aabb.transformed(&self.transform));
+    }
+
+    pub(crate) fn instance_count(&self) -> u32{
+        return self.instances.as_ref().map_or(1, |instances| instances.instance_count);
+    }
+}
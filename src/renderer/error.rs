@@ -0,0 +1,144 @@
+use std::error::Error;
+use std::fmt;
+use vulkano::instance::InstanceCreationError;
+use vulkano::swapchain::SurfaceCreationError;
+use vulkano::LoadingError;
+use crate::renderer::gltf_loader::GltfLoadError;
+use crate::renderer::obj_loader::ObjLoadError;
+use crate::renderer::shader_loader::ShaderLoadError;
+use crate::renderer::ShaderType;
+
+pub type BoxedError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug)]
+pub enum RendererError{
+    LibraryLoading(LoadingError),
+    InstanceCreation(InstanceCreationError),
+    SurfaceCreation(SurfaceCreationError),
+    NoSuitableDevice,
+    Device(BoxedError),
+    Swapchain(BoxedError),
+    Pipeline(BoxedError),
+    ShaderNotFound{ shader_type: ShaderType, name: String },
+    Shader(ShaderLoadError),
+    Allocation(BoxedError),
+    Submission(BoxedError),
+    Readback(BoxedError),
+    // An index buffer references a vertex past the end of the vertex buffer.
+    IndexOutOfRange{ index: u32, vertex_count: usize },
+    // A render target was requested with a zero width or height.
+    InvalidExtent{ width: u32, height: u32 },
+    ObjLoad(ObjLoadError),
+    GltfLoad(GltfLoadError)
+}
+
+impl RendererError {
+    pub(crate) fn device(error: impl Into<BoxedError>) -> Self{
+        return RendererError::Device(error.into());
+    }
+
+    pub(crate) fn swapchain(error: impl Into<BoxedError>) -> Self{
+        return RendererError::Swapchain(error.into());
+    }
+
+    pub(crate) fn pipeline(error: impl Into<BoxedError>) -> Self{
+        return RendererError::Pipeline(error.into());
+    }
+
+    pub(crate) fn allocation(error: impl Into<BoxedError>) -> Self{
+        return RendererError::Allocation(error.into());
+    }
+
+    pub(crate) fn submission(error: impl Into<BoxedError>) -> Self{
+        return RendererError::Submission(error.into());
+    }
+
+    pub(crate) fn readback(error: impl Into<BoxedError>) -> Self{
+        return RendererError::Readback(error.into());
+    }
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        return match self {
+            RendererError::LibraryLoading(error) => write!(f, "failed to load the Vulkan library: {}", error),
+            RendererError::InstanceCreation(error) => write!(f, "failed to create the Vulkan instance: {}", error),
+            RendererError::SurfaceCreation(error) => write!(f, "failed to create the window surface: {}", error),
+            RendererError::NoSuitableDevice => write!(f, "no suitable physical device found"),
+            RendererError::Device(error) => write!(f, "device error: {}", error),
+            RendererError::Swapchain(error) => write!(f, "swapchain error: {}", error),
+            RendererError::Pipeline(error) => write!(f, "failed to build pipeline: {}", error),
+            RendererError::ShaderNotFound{ shader_type, name } =>
+                write!(f, "no {:?} shader named \"{}\" is registered", shader_type, name),
+            RendererError::Shader(error) => write!(f, "{}", error),
+            RendererError::Allocation(error) => write!(f, "allocation failed: {}", error),
+            RendererError::Submission(error) => write!(f, "failed to submit frame: {}", error),
+            RendererError::Readback(error) => write!(f, "failed to read back the rendered image: {}", error),
+            RendererError::IndexOutOfRange{ index, vertex_count } =>
+                write!(f, "index {} is out of range for a model with {} vertices", index, vertex_count),
+            RendererError::InvalidExtent{ width, height } =>
+                write!(f, "render target must be at least 1x1, got {}x{}", width, height),
+            RendererError::ObjLoad(error) => write!(f, "{}", error),
+            RendererError::GltfLoad(error) => write!(f, "{}", error)
+        };
+    }
+}
+
+impl Error for RendererError {
+    fn source(&self) -> Option<&(dyn Error + 'static)>{
+        return match self {
+            RendererError::LibraryLoading(error) => Some(error),
+            RendererError::InstanceCreation(error) => Some(error),
+            RendererError::SurfaceCreation(error) => Some(error),
+            RendererError::NoSuitableDevice => None,
+            RendererError::ShaderNotFound{ .. } => None,
+            RendererError::IndexOutOfRange{ .. } => None,
+            RendererError::InvalidExtent{ .. } => None,
+            RendererError::Shader(error) => Some(error),
+            RendererError::ObjLoad(error) => Some(error),
+            RendererError::GltfLoad(error) => Some(error),
+            RendererError::Device(error)
+            | RendererError::Swapchain(error)
+            | RendererError::Pipeline(error)
+            | RendererError::Allocation(error)
+            | RendererError::Submission(error)
+            | RendererError::Readback(error) => Some(error.as_ref())
+        };
+    }
+}
+
+impl From<LoadingError> for RendererError {
+    fn from(error: LoadingError) -> Self{
+        return RendererError::LibraryLoading(error);
+    }
+}
+
+impl From<InstanceCreationError> for RendererError {
+    fn from(error: InstanceCreationError) -> Self{
+        return RendererError::InstanceCreation(error);
+    }
+}
+
+impl From<SurfaceCreationError> for RendererError {
+    fn from(error: SurfaceCreationError) -> Self{
+        return RendererError::SurfaceCreation(error);
+    }
+}
+
+impl From<ShaderLoadError> for RendererError {
+    fn from(error: ShaderLoadError) -> Self{
+        return RendererError::Shader(error);
+    }
+}
+
+impl From<ObjLoadError> for RendererError {
+    fn from(error: ObjLoadError) -> Self{
+        return RendererError::ObjLoad(error);
+    }
+}
+
+impl From<GltfLoadError> for RendererError {
+    fn from(error: GltfLoadError) -> Self{
+        return RendererError::GltfLoad(error);
+    }
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use bytemuck::Zeroable;
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::layout::DescriptorSetLayout;
+use vulkano::descriptor_set::{DescriptorSet, PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::sync::{FenceSignalFuture, GpuFuture};
+use crate::renderer::error::RendererError;
+use crate::renderer::light::{LIGHT_BINDING, LightData};
+use crate::renderer::shadow::{SHADOW_DATA_BINDING, SHADOW_MAP_BINDING, ShadowData, ShadowMap};
+use crate::renderer::FrameData;
+
+// Everything a frame in flight owns. A slot is only reused once its fence has signalled.
+pub(crate) struct FrameResources{
+    pub(crate) fence: Option<Arc<FenceSignalFuture<Box<dyn GpuFuture>>>>,
+    uniform_buffer: Arc<CpuAccessibleBuffer<FrameData>>,
+    light_buffer: Arc<CpuAccessibleBuffer<LightData>>,
+    shadow_buffer: Arc<CpuAccessibleBuffer<ShadowData>>,
+    // Each frame renders its own shadow maps, so they are never overwritten while an earlier frame still samples them.
+    pub(crate) shadow_map: ShadowMap,
+    // One per set 0 layout, all pointing at this frame's uniform buffers.
+    descriptor_sets: Vec<Arc<PersistentDescriptorSet>>
+}
+
+impl FrameResources {
+    pub(crate) fn new(allocator:&StandardMemoryAllocator, shadow_map:ShadowMap) -> Result<Self, RendererError>{
+        let uniform_buffer = CpuAccessibleBuffer::from_data(
+            allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            FrameData::zeroed(),
+        ).map_err(RendererError::allocation)?;
+
+        let light_buffer = CpuAccessibleBuffer::from_data(
+            allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            LightData::zeroed(),
+        ).map_err(RendererError::allocation)?;
+
+        let shadow_buffer = CpuAccessibleBuffer::from_data(
+            allocator,
+            BufferUsage {
+                uniform_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            ShadowData::zeroed(),
+        ).map_err(RendererError::allocation)?;
+
+        return Ok(Self{
+            fence: None,
+            uniform_buffer: uniform_buffer,
+            light_buffer: light_buffer,
+            shadow_buffer: shadow_buffer,
+            shadow_map: shadow_map,
+            descriptor_sets: Vec::new()
+        });
+    }
+
+    // Blocks until the GPU is done with this slot, which releases its uniform buffer for writing.
+    pub(crate) fn wait(&mut self) -> Result<(), RendererError>{
+        if let Some(fence) = self.fence.take() {
+            fence.wait(None).map_err(RendererError::submission)?;
+        }
+        return Ok(());
+    }
+
+    pub(crate) fn write_frame_data(&mut self, frame_data:FrameData, light_data:LightData, shadow_data:ShadowData) -> Result<(), RendererError>{
+        *self.uniform_buffer.write().map_err(RendererError::allocation)? = frame_data;
+        *self.light_buffer.write().map_err(RendererError::allocation)? = light_data;
+        *self.shadow_buffer.write().map_err(RendererError::allocation)? = shadow_data;
+        // Sets whose layout no pipeline uses anymore, e.g. after a shader reload, are dropped here.
+        self.descriptor_sets.retain(|set| Arc::strong_count(set.layout()) > 1);
+        return Ok(());
+    }
+
+    pub(crate) fn descriptor_set(&mut self, descriptor_set_allocator:&StandardDescriptorSetAllocator, layout:&Arc<DescriptorSetLayout>) -> Result<Arc<PersistentDescriptorSet>, RendererError>{
+        if let Some(set) = self.descriptor_sets.iter().find(|set| Arc::ptr_eq(set.layout(), layout)) {
+            return Ok(set.clone());
+        }
+
+        // Only the bindings the shaders declare are written, so shaders without lighting keep working.
+        let mut writes: Vec<WriteDescriptorSet> = Vec::new();
+        if layout.bindings().contains_key(&0) {
+            writes.push(WriteDescriptorSet::buffer(0, self.uniform_buffer.clone()));
+        }
+        if layout.bindings().contains_key(&LIGHT_BINDING) {
+            writes.push(WriteDescriptorSet::buffer(LIGHT_BINDING, self.light_buffer.clone()));
+        }
+        if layout.bindings().contains_key(&SHADOW_DATA_BINDING) {
+            writes.push(WriteDescriptorSet::buffer(SHADOW_DATA_BINDING, self.shadow_buffer.clone()));
+        }
+        if layout.bindings().contains_key(&SHADOW_MAP_BINDING) {
+            writes.push(WriteDescriptorSet::image_view_sampler(SHADOW_MAP_BINDING, self.shadow_map.view.clone(), self.shadow_map.sampler.clone()));
+        }
+
+        let set = PersistentDescriptorSet::new(
+            descriptor_set_allocator,
+            layout.clone(),
+            writes,
+        ).map_err(RendererError::allocation)?;
+        self.descriptor_sets.push(set.clone());
+        return Ok(set);
+    }
+}
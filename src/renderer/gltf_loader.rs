@@ -0,0 +1,426 @@
+use std::fmt;
+use std::path::Path;
+use nalgebra_glm as glm;
+use nalgebra_glm::{Mat4x4, Vec3};
+use gltf::buffer::Data as BufferData;
+use gltf::image::{Data as ImageData, Format as ImageFormat};
+use gltf::mesh::Mode;
+use gltf::Document;
+use crate::material::Material;
+use crate::renderer::draw_call::DrawCall;
+use crate::renderer::error::RendererError;
+use crate::renderer::model::{MeshVertex, Model};
+use crate::renderer::Renderer;
+
+#[derive(Debug)]
+pub enum GltfLoadError{
+    Gltf(gltf::Error),
+    MissingPositions{ mesh: usize, primitive: usize },
+    // An attribute has a different number of elements than POSITION.
+    AttributeCountMismatch{ mesh: usize, primitive: usize, attribute: &'static str, count: usize, vertex_count: usize },
+    IndexOutOfRange{ mesh: usize, primitive: usize, index: u32, vertex_count: usize },
+    // Points and lines can't be drawn by the triangle pipelines.
+    UnsupportedPrimitiveMode{ mesh: usize, primitive: usize, mode: Mode },
+    UnsupportedImageFormat{ image: usize }
+}
+
+impl fmt::Display for GltfLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        return match self {
+            GltfLoadError::Gltf(error) => write!(f, "failed to import glTF: {}", error),
+            GltfLoadError::MissingPositions{ mesh, primitive } =>
+                write!(f, "glTF mesh {} primitive {} has no POSITION attribute", mesh, primitive),
+            GltfLoadError::AttributeCountMismatch{ mesh, primitive, attribute, count, vertex_count } =>
+                write!(f, "glTF mesh {} primitive {} has {} {} values for {} vertices", mesh, primitive, count, attribute, vertex_count),
+            GltfLoadError::IndexOutOfRange{ mesh, primitive, index, vertex_count } =>
+                write!(f, "glTF mesh {} primitive {} references vertex {} of {}", mesh, primitive, index, vertex_count),
+            GltfLoadError::UnsupportedPrimitiveMode{ mesh, primitive, mode } =>
+                write!(f, "glTF mesh {} primitive {} uses unsupported primitive mode {:?}", mesh, primitive, mode),
+            GltfLoadError::UnsupportedImageFormat{ image } =>
+                write!(f, "glTF image {} uses an unsupported pixel format", image)
+        };
+    }
+}
+
+impl std::error::Error for GltfLoadError {}
+
+impl From<gltf::Error> for GltfLoadError {
+    fn from(error: gltf::Error) -> Self{
+        return GltfLoadError::Gltf(error);
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AlphaMode{
+    Opaque,
+    Mask{ cutoff: f32 },
+    Blend
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportedMaterial{
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub base_color_texture: Option<usize>,
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    pub metallic_roughness_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub normal_scale: f32,
+    pub occlusion_texture: Option<usize>,
+    pub occlusion_strength: f32,
+    pub emissive_factor: [f32; 3],
+    pub emissive_texture: Option<usize>,
+    pub alpha_mode: AlphaMode,
+    pub double_sided: bool
+}
+
+impl Default for ImportedMaterial {
+    fn default() -> Self{
+        return Self{
+            name: None,
+            base_color_factor: [1.0, 1.0, 1.0, 1.0],
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            normal_texture: None,
+            normal_scale: 1.0,
+            occlusion_texture: None,
+            occlusion_strength: 1.0,
+            emissive_factor: [0.0, 0.0, 0.0],
+            emissive_texture: None,
+            alpha_mode: AlphaMode::Opaque,
+            double_sided: false
+        };
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedTexture{
+    pub name: Option<String>,
+    pub width: u32,
+    pub height: u32,
+    pub pixels: Vec<u8>
+}
+
+#[derive(Clone)]
+pub struct ImportedMesh{
+    pub model: Model,
+    pub material: Option<usize>
+}
+
+#[derive(Clone, Debug)]
+pub struct ImportedNode{
+    pub name: Option<String>,
+    pub parent: Option<usize>,
+    pub local_transform: Mat4x4,
+    pub world_transform: Mat4x4,
+    pub meshes: Vec<usize>
+}
+
+#[derive(Clone)]
+pub struct GltfScene{
+    pub meshes: Vec<ImportedMesh>,
+    pub materials: Vec<ImportedMaterial>,
+    pub textures: Vec<ImportedTexture>,
+    pub nodes: Vec<ImportedNode>
+}
+
+impl GltfScene {
+    pub fn load(renderer: &Renderer, path:&Path) -> Result<GltfScene, RendererError>{
+        let (document, buffers, images) = gltf::import(path).map_err(GltfLoadError::from)?;
+        return Self::from_document(renderer, &document, &buffers, &images);
+    }
+
+    pub fn from_slice(renderer: &Renderer, bytes:&[u8]) -> Result<GltfScene, RendererError>{
+        let (document, buffers, images) = gltf::import_slice(bytes).map_err(GltfLoadError::from)?;
+        return Self::from_document(renderer, &document, &buffers, &images);
+    }
+
+    fn from_document(renderer: &Renderer, document:&Document, buffers:&[BufferData], images:&[ImageData]) -> Result<GltfScene, RendererError>{
+        let textures: Vec<ImportedTexture> = document.textures()
+            .map(|texture| {
+                let image_index = texture.source().index();
+                Ok(ImportedTexture{
+                    name: texture.name().map(String::from),
+                    width: images[image_index].width,
+                    height: images[image_index].height,
+                    pixels: convert_to_rgba8(&images[image_index])
+                        .ok_or(GltfLoadError::UnsupportedImageFormat{ image: image_index })?
+                })
+            })
+            .collect::<Result<Vec<_>, GltfLoadError>>()?;
+
+        let materials: Vec<ImportedMaterial> = document.materials().map(import_material).collect();
+
+        let mut meshes: Vec<ImportedMesh> = Vec::new();
+        let mut mesh_primitives: Vec<Vec<usize>> = Vec::new();
+        for mesh in document.meshes() {
+            let mut primitives: Vec<usize> = Vec::new();
+            for primitive in mesh.primitives() {
+                if !matches!(primitive.mode(), Mode::Triangles | Mode::TriangleStrip | Mode::TriangleFan) {
+                    return Err(GltfLoadError::UnsupportedPrimitiveMode{
+                        mesh: mesh.index(),
+                        primitive: primitive.index(),
+                        mode: primitive.mode()
+                    }.into());
+                }
+
+                let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+                let positions: Vec<[f32; 3]> = reader.read_positions()
+                    .ok_or(GltfLoadError::MissingPositions{ mesh: mesh.index(), primitive: primitive.index() })?
+                    .collect();
+                let indices: Vec<u32> = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect(),
+                    None => (0..positions.len() as u32).collect()
+                };
+                if let Some(&index) = indices.iter().find(|&&index| index as usize >= positions.len()) {
+                    return Err(GltfLoadError::IndexOutOfRange{
+                        mesh: mesh.index(),
+                        primitive: primitive.index(),
+                        index: index,
+                        vertex_count: positions.len()
+                    }.into());
+                }
+                let indices = triangulate(primitive.mode(), indices);
+                let normals: Vec<[f32; 3]> = match reader.read_normals() {
+                    Some(normals) => normals.collect(),
+                    None => generate_normals(&positions, &indices)
+                };
+                let uvs: Option<Vec<[f32; 2]>> = reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect());
+                let colors: Option<Vec<[f32; 4]>> = reader.read_colors(0).map(|colors| colors.into_rgba_f32().collect());
+                let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|tangents| tangents.collect());
+
+                let attribute_counts = [
+                    ("NORMAL", Some(normals.len())),
+                    ("TEXCOORD_0", uvs.as_ref().map(Vec::len)),
+                    ("COLOR_0", colors.as_ref().map(Vec::len)),
+                    ("TANGENT", tangents.as_ref().map(Vec::len))
+                ];
+                for (attribute, count) in attribute_counts {
+                    if let Some(count) = count.filter(|&count| count != positions.len()) {
+                        return Err(GltfLoadError::AttributeCountMismatch{
+                            mesh: mesh.index(),
+                            primitive: primitive.index(),
+                            attribute: attribute,
+                            count: count,
+                            vertex_count: positions.len()
+                        }.into());
+                    }
+                }
+
+                let vertices: Vec<MeshVertex> = (0..positions.len())
+                    .map(|i| MeshVertex{
+                        position: positions[i],
+                        normal: normals[i],
+                        uv: uvs.as_ref().map(|uvs| uvs[i]).unwrap_or([0.0, 0.0]),
+                        color: colors.as_ref().map(|colors| colors[i]).unwrap_or([1.0, 1.0, 1.0, 1.0]),
+                        tangent: tangents.as_ref().map(|tangents| tangents[i]).unwrap_or([1.0, 0.0, 0.0, 1.0])
+                    })
+                    .collect();
+
+                primitives.push(meshes.len());
+                meshes.push(ImportedMesh{
+                    model: Model::load_indexed(renderer, vertices, indices)?,
+                    material: primitive.material().index()
+                });
+            }
+            mesh_primitives.push(primitives);
+        }
+
+        let mut nodes: Vec<ImportedNode> = Vec::new();
+        let scene = document.default_scene().or_else(|| document.scenes().next());
+        if let Some(scene) = scene {
+            for node in scene.nodes() {
+                import_node(&node, None, &Mat4x4::identity(), &mesh_primitives, &mut nodes);
+            }
+        }
+
+        return Ok(GltfScene{
+            meshes: meshes,
+            materials: materials,
+            textures: textures,
+            nodes: nodes
+        });
+    }
+
+    // `materials` is parallel to `self.materials`; primitives without a usable entry get `default_material`.
+    pub fn draw_calls(&self, materials:&[Material], default_material:&Material) -> Vec<DrawCall>{
+        let mut draw_calls: Vec<DrawCall> = Vec::new();
+        for node in &self.nodes {
+            for &mesh_index in &node.meshes {
+                let mesh = &self.meshes[mesh_index];
+                let material = mesh.material
+                    .and_then(|material| materials.get(material))
+                    .unwrap_or(default_material);
+                draw_calls.push(DrawCall::new(node.world_transform, mesh.model.clone(), material.clone()));
+            }
+        }
+        return draw_calls;
+    }
+}
+
+fn import_material(material: gltf::Material) -> ImportedMaterial{
+    let pbr = material.pbr_metallic_roughness();
+    return ImportedMaterial{
+        name: material.name().map(String::from),
+        base_color_factor: pbr.base_color_factor(),
+        base_color_texture: pbr.base_color_texture().map(|info| info.texture().index()),
+        metallic_factor: pbr.metallic_factor(),
+        roughness_factor: pbr.roughness_factor(),
+        metallic_roughness_texture: pbr.metallic_roughness_texture().map(|info| info.texture().index()),
+        normal_texture: material.normal_texture().map(|info| info.texture().index()),
+        normal_scale: material.normal_texture().map(|info| info.scale()).unwrap_or(1.0),
+        occlusion_texture: material.occlusion_texture().map(|info| info.texture().index()),
+        occlusion_strength: material.occlusion_texture().map(|info| info.strength()).unwrap_or(1.0),
+        emissive_factor: material.emissive_factor(),
+        emissive_texture: material.emissive_texture().map(|info| info.texture().index()),
+        alpha_mode: match material.alpha_mode() {
+            gltf::material::AlphaMode::Opaque => AlphaMode::Opaque,
+            gltf::material::AlphaMode::Mask => AlphaMode::Mask{ cutoff: material.alpha_cutoff().unwrap_or(0.5) },
+            gltf::material::AlphaMode::Blend => AlphaMode::Blend
+        },
+        double_sided: material.double_sided()
+    };
+}
+
+fn import_node(node: &gltf::Node, parent: Option<usize>, parent_transform: &Mat4x4, mesh_primitives: &[Vec<usize>], nodes: &mut Vec<ImportedNode>){
+    let local_transform: Mat4x4 = Mat4x4::from(node.transform().matrix());
+    let world_transform: Mat4x4 = parent_transform * local_transform;
+    let index = nodes.len();
+    nodes.push(ImportedNode{
+        name: node.name().map(String::from),
+        parent: parent,
+        local_transform: local_transform,
+        world_transform: world_transform,
+        meshes: node.mesh().map(|mesh| mesh_primitives[mesh.index()].clone()).unwrap_or_default()
+    });
+
+    for child in node.children() {
+        import_node(&child, Some(index), &world_transform, mesh_primitives, nodes);
+    }
+}
+
+// Every index has to be in range of `positions`.
+// Rewrites strip and fan indices as a triangle list, keeping the winding of every triangle.
+fn triangulate(mode: Mode, indices: Vec<u32>) -> Vec<u32>{
+    let triangle_count = indices.len().saturating_sub(2);
+    return match mode {
+        Mode::TriangleStrip => (0..triangle_count)
+            .flat_map(|i| if i % 2 == 0 {
+                [indices[i], indices[i + 1], indices[i + 2]]
+            } else {
+                [indices[i + 1], indices[i], indices[i + 2]]
+            })
+            .collect(),
+        Mode::TriangleFan => (0..triangle_count)
+            .flat_map(|i| [indices[0], indices[i + 1], indices[i + 2]])
+            .collect(),
+        _ => indices
+    };
+}
+
+fn generate_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]>{
+    let mut normals: Vec<Vec3> = vec![Vec3::zeros(); positions.len()];
+    for triangle in indices.chunks_exact(3) {
+        let a: Vec3 = Vec3::from(positions[triangle[0] as usize]);
+        let b: Vec3 = Vec3::from(positions[triangle[1] as usize]);
+        let c: Vec3 = Vec3::from(positions[triangle[2] as usize]);
+        let face_normal: Vec3 = glm::cross(&(b - a), &(c - a));
+        for &index in triangle {
+            normals[index as usize] += face_normal;
+        }
+    }
+    return normals.iter()
+        .map(|normal| if normal.norm_squared() > 0.0 { glm::normalize(normal).into() } else { [0.0, 0.0, 1.0] })
+        .collect();
+}
+
+fn convert_to_rgba8(image: &ImageData) -> Option<Vec<u8>>{
+    let pixels = &image.pixels;
+    // 16-bit channels come in native byte order, only their high byte is kept.
+    let high_byte = |p:&[u8], channel:usize| -> u8 { (u16::from_ne_bytes([p[channel * 2], p[channel * 2 + 1]]) >> 8) as u8 };
+    let rgba: Vec<u8> = match image.format {
+        ImageFormat::R8G8B8A8 => pixels.clone(),
+        ImageFormat::R8G8B8 => pixels.chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        // One and two channel images are grayscale, optionally with alpha.
+        ImageFormat::R8G8 => pixels.chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        ImageFormat::R8 => pixels.iter()
+            .flat_map(|&p| [p, p, p, 255])
+            .collect(),
+        ImageFormat::R16G16B16A16 => pixels.chunks_exact(8)
+            .flat_map(|p| [high_byte(p, 0), high_byte(p, 1), high_byte(p, 2), high_byte(p, 3)])
+            .collect(),
+        ImageFormat::R16G16B16 => pixels.chunks_exact(6)
+            .flat_map(|p| [high_byte(p, 0), high_byte(p, 1), high_byte(p, 2), 255])
+            .collect(),
+        ImageFormat::R16G16 => pixels.chunks_exact(4)
+            .flat_map(|p| { let luma = high_byte(p, 0); [luma, luma, luma, high_byte(p, 1)] })
+            .collect(),
+        ImageFormat::R16 => pixels.chunks_exact(2)
+            .flat_map(|p| { let luma = high_byte(p, 0); [luma, luma, luma, 255] })
+            .collect(),
+        _ => return None
+    };
+    return Some(rgba);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert(format:ImageFormat, pixels:Vec<u8>) -> Option<Vec<u8>>{
+        return convert_to_rgba8(&ImageData{ pixels: pixels, format: format, width: 1, height: 1 });
+    }
+
+    fn wide(values:&[u16]) -> Vec<u8>{
+        return values.iter().flat_map(|value| value.to_ne_bytes()).collect();
+    }
+
+    #[test]
+    fn eight_bit_formats(){
+        assert_eq!(convert(ImageFormat::R8G8B8A8, vec![1, 2, 3, 4]), Some(vec![1, 2, 3, 4]));
+        assert_eq!(convert(ImageFormat::R8G8B8, vec![1, 2, 3]), Some(vec![1, 2, 3, 255]));
+        assert_eq!(convert(ImageFormat::R8G8, vec![7, 9]), Some(vec![7, 7, 7, 9]));
+        assert_eq!(convert(ImageFormat::R8, vec![7]), Some(vec![7, 7, 7, 255]));
+    }
+
+    #[test]
+    fn sixteen_bit_formats_keep_the_high_byte(){
+        assert_eq!(convert(ImageFormat::R16G16B16A16, wide(&[0x0102, 0x0304, 0x0506, 0xFF00])), Some(vec![1, 3, 5, 255]));
+        assert_eq!(convert(ImageFormat::R16G16B16, wide(&[0x0102, 0x0304, 0x0506])), Some(vec![1, 3, 5, 255]));
+        assert_eq!(convert(ImageFormat::R16G16, wide(&[0x8001, 0x4002])), Some(vec![0x80, 0x80, 0x80, 0x40]));
+        assert_eq!(convert(ImageFormat::R16, wide(&[0x8001])), Some(vec![0x80, 0x80, 0x80, 255]));
+    }
+
+    #[test]
+    fn strips_alternate_winding(){
+        assert_eq!(triangulate(Mode::TriangleStrip, vec![0, 1, 2, 3, 4]), vec![0, 1, 2, 2, 1, 3, 2, 3, 4]);
+    }
+
+    #[test]
+    fn fans_share_the_first_vertex(){
+        assert_eq!(triangulate(Mode::TriangleFan, vec![0, 1, 2, 3, 4]), vec![0, 1, 2, 0, 2, 3, 0, 3, 4]);
+    }
+
+    #[test]
+    fn short_strips_and_fans_are_empty(){
+        assert!(triangulate(Mode::TriangleStrip, vec![0, 1]).is_empty());
+        assert!(triangulate(Mode::TriangleFan, vec![]).is_empty());
+    }
+
+    #[test]
+    fn triangle_lists_are_unchanged(){
+        assert_eq!(triangulate(Mode::Triangles, vec![0, 1, 2, 2, 1, 3]), vec![0, 1, 2, 2, 1, 3]);
+    }
+
+    #[test]
+    fn float_formats_are_unsupported(){
+        assert_eq!(convert(ImageFormat::R32G32B32FLOAT, vec![0; 12]), None);
+    }
+}
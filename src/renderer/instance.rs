@@ -0,0 +1,58 @@
+use std::sync::Arc;
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm::Mat4x4;
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer};
+use vulkano::impl_vertex;
+use vulkano::pipeline::graphics::vertex_input::Vertex as VertexType;
+use crate::renderer::error::RendererError;
+use crate::renderer::Renderer;
+
+// Per-instance attributes, bound as the second vertex binding with an instance input rate.
+pub trait InstanceFormat: VertexType + Pod + Send + Sync {}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct InstanceData{
+    pub instance_transform: [[f32; 4]; 4],
+    pub instance_color: [f32; 4]
+}
+
+impl_vertex!(InstanceData, instance_transform, instance_color);
+
+impl InstanceFormat for InstanceData {}
+
+impl InstanceData {
+    pub fn new(transform:Mat4x4, color:[f32; 4]) -> Self{
+        return Self{
+            instance_transform: transform.into(),
+            instance_color: color
+        };
+    }
+}
+
+#[derive(Clone)]
+pub struct InstanceBuffer{
+    pub buffer: Arc<dyn BufferAccess>,
+    pub instance_count: u32
+}
+
+impl InstanceBuffer {
+    pub fn load<I: InstanceFormat>(renderer:&Renderer, instances:Vec<I>) -> Result<InstanceBuffer, RendererError>{
+        let instance_count = instances.len() as u32;
+        let buffer = CpuAccessibleBuffer::from_iter(
+            &renderer.allocator,
+            BufferUsage {
+                vertex_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            instances,
+        ).map_err(RendererError::allocation)?;
+        renderer.set_debug_name(buffer.inner().buffer.as_ref(), "instance buffer");
+
+        return Ok(InstanceBuffer{
+            buffer: buffer,
+            instance_count: instance_count
+        });
+    }
+}
@@ -0,0 +1,144 @@
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm as glm;
+use nalgebra_glm::Vec3;
+
+// Lights beyond this count are ignored. Must match `MAX_LIGHTS` in the lit shaders.
+pub const MAX_LIGHTS: usize = 16;
+
+// Set 0 binding 1 in every shader that declares it; binding 0 stays the per-frame camera data.
+pub const LIGHT_BINDING: u32 = 1;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Light{
+    Directional{
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        cast_shadows: bool
+    },
+    Point{
+        position: Vec3,
+        color: Vec3,
+        intensity: f32,
+        // Distance at which the light has faded out completely.
+        range: f32
+    },
+    Spot{
+        position: Vec3,
+        direction: Vec3,
+        color: Vec3,
+        intensity: f32,
+        range: f32,
+        // Half angles in radians. Full intensity inside the inner cone, fading to zero at the outer one.
+        inner_angle: f32,
+        outer_angle: f32,
+        cast_shadows: bool
+    }
+}
+
+impl Light {
+    pub fn directional(direction:Vec3, color:Vec3, intensity:f32) -> Self{
+        return Light::Directional{ direction, color, intensity, cast_shadows: false };
+    }
+
+    pub fn point(position:Vec3, color:Vec3, intensity:f32, range:f32) -> Self{
+        return Light::Point{ position, color, intensity, range };
+    }
+
+    pub fn spot(position:Vec3, direction:Vec3, color:Vec3, intensity:f32, range:f32, inner_angle:f32, outer_angle:f32) -> Self{
+        return Light::Spot{ position, direction, color, intensity, range, inner_angle, outer_angle, cast_shadows: false };
+    }
+
+    // Only directional and spot lights cast shadows, see `ShadowSettings` for how many of them get a shadow map.
+    pub fn with_shadows(mut self, shadows:bool) -> Self{
+        match &mut self {
+            Light::Directional{ cast_shadows, .. } | Light::Spot{ cast_shadows, .. } => *cast_shadows = shadows,
+            Light::Point{ .. } => {}
+        }
+        return self;
+    }
+
+    pub fn casts_shadows(&self) -> bool{
+        return match *self {
+            Light::Directional{ cast_shadows, .. } | Light::Spot{ cast_shadows, .. } => cast_shadows,
+            Light::Point{ .. } => false
+        };
+    }
+
+    fn to_gpu(&self) -> GpuLight{
+        return match *self {
+            Light::Directional{ direction, color, intensity, .. } => GpuLight{
+                position_range: [0.0, 0.0, 0.0, 0.0],
+                direction_type: direction_type(direction, LIGHT_TYPE_DIRECTIONAL),
+                color_intensity: [color.x, color.y, color.z, intensity],
+                cone: [0.0, 0.0, 0.0, 0.0]
+            },
+            Light::Point{ position, color, intensity, range } => GpuLight{
+                position_range: [position.x, position.y, position.z, range],
+                direction_type: [0.0, 0.0, 0.0, LIGHT_TYPE_POINT],
+                color_intensity: [color.x, color.y, color.z, intensity],
+                cone: [0.0, 0.0, 0.0, 0.0]
+            },
+            Light::Spot{ position, direction, color, intensity, range, inner_angle, outer_angle, .. } => GpuLight{
+                position_range: [position.x, position.y, position.z, range],
+                direction_type: direction_type(direction, LIGHT_TYPE_SPOT),
+                color_intensity: [color.x, color.y, color.z, intensity],
+                cone: [inner_angle.cos(), outer_angle.max(inner_angle).cos(), 0.0, 0.0]
+            }
+        };
+    }
+}
+
+const LIGHT_TYPE_DIRECTIONAL: f32 = 0.0;
+const LIGHT_TYPE_POINT: f32 = 1.0;
+const LIGHT_TYPE_SPOT: f32 = 2.0;
+
+// A zero length direction points straight down instead of turning into NaN.
+pub(crate) fn normalize_direction(direction:Vec3) -> Vec3{
+    return if direction.norm_squared() > 0.0 { glm::normalize(&direction) } else { Vec3::new(0.0, -1.0, 0.0) };
+}
+
+fn direction_type(direction:Vec3, light_type:f32) -> [f32; 4]{
+    let direction: Vec3 = normalize_direction(direction);
+    return [direction.x, direction.y, direction.z, light_type];
+}
+
+// std140 layout of `struct Light` in the lit shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+struct GpuLight{
+    position_range: [f32; 4],
+    direction_type: [f32; 4],
+    color_intensity: [f32; 4],
+    // Cos inner angle, cos outer angle, first shadow map layer, shadow map layer count.
+    cone: [f32; 4]
+}
+
+// std140 layout of the `LightData` uniform block.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Zeroable, Pod)]
+pub(crate) struct LightData{
+    lights: [GpuLight; MAX_LIGHTS],
+    count: [u32; 4],
+    ambient: [f32; 4]
+}
+
+impl LightData {
+    pub(crate) fn new(lights:&[Light], ambient:Vec3) -> Self{
+        let mut light_data = LightData::zeroed();
+        for (gpu_light, light) in light_data.lights.iter_mut().zip(lights) {
+            *gpu_light = light.to_gpu();
+        }
+        light_data.count[0] = lights.len().min(MAX_LIGHTS) as u32;
+        light_data.ambient = [ambient.x, ambient.y, ambient.z, 1.0];
+        return light_data;
+    }
+
+    // A layer count of zero, the default, leaves the light unshadowed.
+    pub(crate) fn set_shadow(&mut self, index:usize, first_layer:u32, layer_count:u32){
+        if let Some(light) = self.lights.get_mut(index) {
+            light.cone[2] = first_layer as f32;
+            light.cone[3] = layer_count as f32;
+        }
+    }
+}
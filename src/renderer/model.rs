@@ -1,9 +1,16 @@
 use std::sync::Arc;
-use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::buffer::{BufferAccess, BufferUsage, CpuAccessibleBuffer, TypedBufferAccess};
 use vulkano::impl_vertex;
+use vulkano::pipeline::graphics::vertex_input::Vertex as VertexType;
 use bytemuck::{Pod, Zeroable};
+use crate::renderer::bounds::{Aabb, BoundingSphere};
+use crate::renderer::error::RendererError;
 use crate::renderer::Renderer;
 
+pub trait VertexFormat: VertexType + Pod + Send + Sync {
+    fn position(&self) -> [f32; 3];
+}
+
 #[repr(C)]
 #[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
 pub struct Vertex{
@@ -12,14 +19,135 @@ pub struct Vertex{
 
 impl_vertex!(Vertex, position);
 
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct ColoredVertex{
+    pub position: [f32; 3],
+    pub color: [f32; 4]
+}
+
+impl_vertex!(ColoredVertex, position, color);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct TexturedVertex{
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2]
+}
+
+impl_vertex!(TexturedVertex, position, normal, uv);
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Zeroable, Pod)]
+pub struct MeshVertex{
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+    pub uv: [f32; 2],
+    pub color: [f32; 4],
+    pub tangent: [f32; 4]
+}
+
+impl_vertex!(MeshVertex, position, normal, uv, color, tangent);
+
+impl VertexFormat for Vertex {
+    fn position(&self) -> [f32; 3]{
+        return self.position;
+    }
+}
+
+impl VertexFormat for ColoredVertex {
+    fn position(&self) -> [f32; 3]{
+        return self.position;
+    }
+}
+
+impl VertexFormat for TexturedVertex {
+    fn position(&self) -> [f32; 3]{
+        return self.position;
+    }
+}
+
+impl VertexFormat for MeshVertex {
+    fn position(&self) -> [f32; 3]{
+        return self.position;
+    }
+}
+
+#[derive(Clone)]
+pub enum IndexBuffer{
+    U16(Arc<CpuAccessibleBuffer<[u16]>>),
+    U32(Arc<CpuAccessibleBuffer<[u32]>>)
+}
+
+impl IndexBuffer {
+    pub fn len(&self) -> u64{
+        return match self {
+            IndexBuffer::U16(buffer) => buffer.len(),
+            IndexBuffer::U32(buffer) => buffer.len()
+        };
+    }
+
+    pub(crate) fn id(&self) -> usize{
+        return match self {
+            IndexBuffer::U16(buffer) => Arc::as_ptr(buffer) as *const () as usize,
+            IndexBuffer::U32(buffer) => Arc::as_ptr(buffer) as *const () as usize
+        };
+    }
+}
+
 #[derive(Clone)]
 pub struct Model{
-    pub buffer: Arc<CpuAccessibleBuffer<[Vertex]>>
+    pub buffer: Arc<dyn BufferAccess>,
+    pub vertex_count: u32,
+    pub indices: Option<IndexBuffer>,
+    // Object space bounds, computed from the vertex positions when the model is loaded.
+    pub aabb: Aabb,
+    pub bounding_sphere: BoundingSphere
 }
 
 impl Model {
-    pub fn load(renderer: &Renderer, vertices:Vec<Vertex>) -> Model{
-        let vertex_buffer: Arc<CpuAccessibleBuffer<[Vertex]>> = CpuAccessibleBuffer::from_iter(
+    pub fn load<V: VertexFormat>(renderer: &Renderer, vertices:Vec<V>) -> Result<Model, RendererError>{
+        let (aabb, bounding_sphere) = Self::compute_bounds(&vertices);
+        return Ok(Model{
+            vertex_count:vertices.len() as u32,
+            buffer:Self::create_vertex_buffer(renderer, vertices)?,
+            indices:None,
+            aabb:aabb,
+            bounding_sphere:bounding_sphere
+        });
+    }
+
+    pub fn load_indexed<V: VertexFormat>(renderer: &Renderer, vertices:Vec<V>, indices:Vec<u32>) -> Result<Model, RendererError>{
+        if let Some(&index) = indices.iter().find(|&&index| index as usize >= vertices.len()) {
+            return Err(RendererError::IndexOutOfRange{ index: index, vertex_count: vertices.len() });
+        }
+
+        // Every index is below the vertex count, so it fits in 16 bits whenever the count does.
+        let index_buffer: IndexBuffer = if vertices.len() <= u16::MAX as usize {
+            IndexBuffer::U16(Self::create_index_buffer(renderer, indices.into_iter().map(|index| index as u16))?)
+        } else {
+            IndexBuffer::U32(Self::create_index_buffer(renderer, indices.into_iter())?)
+        };
+
+        let (aabb, bounding_sphere) = Self::compute_bounds(&vertices);
+        return Ok(Model{
+            vertex_count:vertices.len() as u32,
+            buffer:Self::create_vertex_buffer(renderer, vertices)?,
+            indices:Some(index_buffer),
+            aabb:aabb,
+            bounding_sphere:bounding_sphere
+        });
+    }
+
+    fn compute_bounds<V: VertexFormat>(vertices:&[V]) -> (Aabb, BoundingSphere){
+        let aabb = Aabb::from_points(vertices.iter().map(VertexFormat::position));
+        let bounding_sphere = BoundingSphere::from_points(&aabb, vertices.iter().map(VertexFormat::position));
+        return (aabb, bounding_sphere);
+    }
+
+    fn create_vertex_buffer<V: VertexFormat>(renderer: &Renderer, vertices:Vec<V>) -> Result<Arc<CpuAccessibleBuffer<[V]>>, RendererError>{
+        let buffer = CpuAccessibleBuffer::from_iter(
             &renderer.allocator,
             BufferUsage {
                 vertex_buffer: true,
@@ -27,14 +155,26 @@ impl Model {
             },
             false,
             vertices,
-        ).unwrap();
+        ).map_err(RendererError::allocation)?;
+        renderer.set_debug_name(buffer.inner().buffer.as_ref(), "model vertex buffer");
+        return Ok(buffer);
+    }
 
-        return Model{
-            buffer:vertex_buffer
-        }
+    fn create_index_buffer<I: Pod + Send + Sync>(renderer: &Renderer, indices:impl ExactSizeIterator<Item = I>) -> Result<Arc<CpuAccessibleBuffer<[I]>>, RendererError>{
+        let buffer = CpuAccessibleBuffer::from_iter(
+            &renderer.allocator,
+            BufferUsage {
+                index_buffer: true,
+                ..BufferUsage::empty()
+            },
+            false,
+            indices,
+        ).map_err(RendererError::allocation)?;
+        renderer.set_debug_name(buffer.inner().buffer.as_ref(), "model index buffer");
+        return Ok(buffer);
     }
 
-    pub fn star(renderer: &Renderer) -> Model{
+    pub fn star(renderer: &Renderer) -> Result<Model, RendererError>{
         let vertices = vec![
             Vertex {
                 position: [-0.75, 1.0, 0.5],
@@ -54,20 +194,19 @@ impl Model {
             Vertex {
                 position: [0.75, 1.0, 0.5],
             },
-            Vertex {
-                position: [0.2, -0.5, 0.5],
-            },
             Vertex {
                 position: [1.0, -0.5, 0.5],
             },
-            Vertex {
-                position: [0.4, 0.0, 0.5],
-            },
         ];
-        return Self::load(renderer, vertices);
+        let indices = vec![
+            0, 1, 2,
+            3, 4, 5,
+            4, 6, 2
+        ];
+        return Self::load_indexed(renderer, vertices, indices);
     }
 
-    pub fn square(renderer: &Renderer, side_length:f32) -> Model{
+    pub fn square(renderer: &Renderer, side_length:f32) -> Result<Model, RendererError>{
         let vertices = vec![
             Vertex {
                 position: [-side_length, side_length, 0f32],
@@ -78,16 +217,14 @@ impl Model {
             Vertex {
                 position: [side_length, -side_length, 0f32],
             },
-            Vertex {
-                position: [-side_length, side_length, 0f32],
-            },
-            Vertex {
-                position: [side_length, -side_length, 0f32],
-            },
             Vertex {
                 position: [side_length, side_length, 0f32],
             }
         ];
-        return Self::load(renderer, vertices);
+        let indices = vec![
+            0, 1, 2,
+            0, 2, 3
+        ];
+        return Self::load_indexed(renderer, vertices, indices);
     }
 }
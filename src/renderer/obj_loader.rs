@@ -0,0 +1,373 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::path::Path;
+use nalgebra_glm as glm;
+use nalgebra_glm::Vec3;
+use obj::raw::object::{Group, Polygon, RawObj};
+use obj::raw::parse_obj;
+use obj::ObjError;
+use crate::renderer::error::RendererError;
+use crate::renderer::model::{Model, TexturedVertex};
+use crate::renderer::Renderer;
+
+#[derive(Debug)]
+pub enum ObjLoadError{
+    Io(std::io::Error),
+    Parse(ObjError),
+    IndexOutOfRange{ attribute: &'static str, index: usize },
+    DegeneratePolygon{ polygon: usize },
+    NoGeometry
+}
+
+impl fmt::Display for ObjLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        return match self {
+            ObjLoadError::Io(error) => write!(f, "failed to read OBJ file: {}", error),
+            ObjLoadError::Parse(error) => write!(f, "failed to parse OBJ file: {}", error),
+            ObjLoadError::IndexOutOfRange{ attribute, index } =>
+                write!(f, "OBJ face references {} {} which does not exist", attribute, index),
+            ObjLoadError::DegeneratePolygon{ polygon } =>
+                write!(f, "OBJ face {} has fewer than three vertices", polygon),
+            ObjLoadError::NoGeometry => write!(f, "OBJ file contains no faces")
+        };
+    }
+}
+
+impl std::error::Error for ObjLoadError {}
+
+impl From<std::io::Error> for ObjLoadError {
+    fn from(error: std::io::Error) -> Self{
+        return ObjLoadError::Io(error);
+    }
+}
+
+impl From<ObjError> for ObjLoadError {
+    fn from(error: ObjError) -> Self{
+        return ObjLoadError::Parse(error);
+    }
+}
+
+impl Model {
+    pub fn from_obj<R: BufRead>(renderer: &Renderer, reader:R) -> Result<Model, RendererError>{
+        let raw: RawObj = parse_obj(reader).map_err(ObjLoadError::from)?;
+        let polygons: Vec<usize> = (0..raw.polygons.len()).collect();
+        let (vertices, indices) = build_geometry(&raw, &polygons)?;
+        if indices.is_empty() {
+            return Err(ObjLoadError::NoGeometry.into());
+        }
+        return Model::load_indexed(renderer, vertices, indices);
+    }
+
+    pub fn from_obj_file(renderer: &Renderer, path:&Path) -> Result<Model, RendererError>{
+        let file = File::open(path).map_err(ObjLoadError::from)?;
+        return Self::from_obj(renderer, BufReader::new(file));
+    }
+
+    // Splits the file along its `o` objects, or along its `g` groups if it has none, returned in the order they appear
+    // in the file. Faces before the first `o` form an object with an empty name.
+    pub fn from_obj_groups<R: BufRead>(renderer: &Renderer, mut reader:R) -> Result<Vec<(String, Model)>, RendererError>{
+        // The source is scanned twice, the parser does not keep track of where each `o` starts.
+        let mut source = String::new();
+        reader.read_to_string(&mut source).map_err(ObjLoadError::from)?;
+        let raw: RawObj = parse_obj(source.as_bytes()).map_err(ObjLoadError::from)?;
+
+        let mut models: Vec<(String, Model)> = Vec::new();
+        for (name, polygons) in obj_groups(&source, &raw) {
+            let (vertices, indices) = build_geometry(&raw, &polygons)?;
+            if !indices.is_empty() {
+                models.push((name, Model::load_indexed(renderer, vertices, indices)?));
+            }
+        }
+
+        if models.is_empty() {
+            return Err(ObjLoadError::NoGeometry.into());
+        }
+        return Ok(models);
+    }
+}
+
+// Polygon indices of every `o` object, or of every `g` group if the file names no objects.
+fn obj_groups(source: &str, raw: &RawObj) -> Vec<(String, Vec<usize>)>{
+    let objects = object_sets(source);
+    if objects.iter().any(|(name, _)| !name.is_empty()) {
+        return objects;
+    }
+    return polygon_sets(&raw.groups);
+}
+
+// `RawObj::meshes` follows `usemtl` rather than `o`, so objects are found by counting faces the way the parser does:
+// comments stripped, backslash continuations joined, one polygon per `f` or `fo` statement.
+fn object_sets(source: &str) -> Vec<(String, Vec<usize>)>{
+    let mut objects: Vec<(String, Vec<usize>)> = Vec::new();
+    let mut current = String::new();
+    let mut polygon_count: usize = 0;
+    let mut statement = String::new();
+    for line in source.lines() {
+        let line = line.split('#').next().unwrap_or_default();
+        if let Some(continued) = line.strip_suffix('\\') {
+            statement.push_str(continued);
+            statement.push(' ');
+            continue;
+        }
+        statement.push_str(line);
+
+        let mut words = statement.split_whitespace();
+        match words.next() {
+            Some("o") => current = words.collect::<Vec<&str>>().join(" "),
+            Some("f") | Some("fo") => {
+                match objects.iter_mut().find(|(name, _)| *name == current) {
+                    Some((_, polygons)) => polygons.push(polygon_count),
+                    None => objects.push((current.clone(), vec![polygon_count]))
+                }
+                polygon_count += 1;
+            }
+            _ => {}
+        }
+        statement.clear();
+    }
+    return objects;
+}
+
+// Non-empty sets sorted by their first polygon.
+fn polygon_sets(sets: &HashMap<String, Group>) -> Vec<(String, Vec<usize>)>{
+    let mut polygon_sets: Vec<(String, Vec<usize>)> = sets.iter()
+        .map(|(name, group)| {
+            let polygons: Vec<usize> = group.polygons.iter()
+                .flat_map(|range| range.start..range.end)
+                .collect();
+            (name.clone(), polygons)
+        })
+        .filter(|(_, polygons)| !polygons.is_empty())
+        .collect();
+    polygon_sets.sort_by_key(|(_, polygons)| polygons[0]);
+    return polygon_sets;
+}
+
+// Polygons are triangulated as fans around their first corner, which is only correct for convex polygons.
+// Concave n-gons have to be triangulated before export.
+fn build_geometry(raw: &RawObj, polygons: &[usize]) -> Result<(Vec<TexturedVertex>, Vec<u32>), ObjLoadError>{
+    let mut vertices: Vec<TexturedVertex> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+    let mut vertex_lookup: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let mut generated_normals: Vec<bool> = Vec::new();
+
+    for &polygon_index in polygons {
+        let corners: Vec<(usize, Option<usize>, Option<usize>)> = match &raw.polygons[polygon_index] {
+            Polygon::P(corners) => corners.iter().map(|&p| (p, None, None)).collect(),
+            Polygon::PT(corners) => corners.iter().map(|&(p, t)| (p, Some(t), None)).collect(),
+            Polygon::PN(corners) => corners.iter().map(|&(p, n)| (p, None, Some(n))).collect(),
+            Polygon::PTN(corners) => corners.iter().map(|&(p, t, n)| (p, Some(t), Some(n))).collect()
+        };
+        if corners.len() < 3 {
+            return Err(ObjLoadError::DegeneratePolygon{ polygon: polygon_index });
+        }
+
+        let mut corner_indices: Vec<u32> = Vec::with_capacity(corners.len());
+        for corner in &corners {
+            let index = match vertex_lookup.get(corner) {
+                Some(&index) => index,
+                None => {
+                    let index = vertices.len() as u32;
+                    vertices.push(build_vertex(raw, *corner)?);
+                    generated_normals.push(corner.2.is_none());
+                    vertex_lookup.insert(*corner, index);
+                    index
+                }
+            };
+            corner_indices.push(index);
+        }
+
+        let face_normal: Vec3 = polygon_normal(&vertices, &corner_indices);
+        for (corner, &index) in corners.iter().zip(&corner_indices) {
+            if corner.2.is_none() {
+                let normal = &mut vertices[index as usize].normal;
+                normal[0] += face_normal.x;
+                normal[1] += face_normal.y;
+                normal[2] += face_normal.z;
+            }
+        }
+
+        for i in 1..corner_indices.len() - 1 {
+            indices.push(corner_indices[0]);
+            indices.push(corner_indices[i]);
+            indices.push(corner_indices[i + 1]);
+        }
+    }
+
+    for (vertex, generated) in vertices.iter_mut().zip(generated_normals) {
+        if generated {
+            let normal: Vec3 = Vec3::from(vertex.normal);
+            if normal.norm_squared() > 0.0 {
+                vertex.normal = glm::normalize(&normal).into();
+            }
+        }
+    }
+
+    return Ok((vertices, indices));
+}
+
+fn build_vertex(raw: &RawObj, (position, uv, normal): (usize, Option<usize>, Option<usize>)) -> Result<TexturedVertex, ObjLoadError>{
+    let position = raw.positions.get(position)
+        .ok_or(ObjLoadError::IndexOutOfRange{ attribute: "position", index: position })?;
+
+    // OBJ texture coordinates start at the bottom-left, Vulkan samples from the top-left.
+    let uv = match uv {
+        Some(uv) => {
+            let uv = raw.tex_coords.get(uv)
+                .ok_or(ObjLoadError::IndexOutOfRange{ attribute: "texture coordinate", index: uv })?;
+            [uv.0, 1.0 - uv.1]
+        }
+        None => [0.0, 0.0]
+    };
+
+    let normal = match normal {
+        Some(normal) => {
+            let normal = raw.normals.get(normal)
+                .ok_or(ObjLoadError::IndexOutOfRange{ attribute: "normal", index: normal })?;
+            [normal.0, normal.1, normal.2]
+        }
+        None => [0.0, 0.0, 0.0]
+    };
+
+    return Ok(TexturedVertex{
+        position: [position.0, position.1, position.2],
+        normal: normal,
+        uv: uv
+    });
+}
+
+// Newell's method, so concave and slightly non-planar faces still get a sensible normal.
+fn polygon_normal(vertices: &[TexturedVertex], corner_indices: &[u32]) -> Vec3{
+    let mut normal: Vec3 = Vec3::zeros();
+    for i in 0..corner_indices.len() {
+        let current: Vec3 = Vec3::from(vertices[corner_indices[i] as usize].position);
+        let next: Vec3 = Vec3::from(vertices[corner_indices[(i + 1) % corner_indices.len()] as usize].position);
+        normal.x += (current.y - next.y) * (current.z + next.z);
+        normal.y += (current.z - next.z) * (current.x + next.x);
+        normal.z += (current.x - next.x) * (current.y + next.y);
+    }
+    return normal;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUAD: &str = "
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+";
+
+    fn parse(source:&str) -> RawObj{
+        return parse_obj(source.as_bytes()).unwrap();
+    }
+
+    fn group_names(source:&str) -> Vec<(String, Vec<usize>)>{
+        return obj_groups(source, &parse(source));
+    }
+
+    #[test]
+    fn quads_become_two_triangles(){
+        let raw = parse(QUAD);
+        let (vertices, indices) = build_geometry(&raw, &[0]).unwrap();
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, vec![0, 1, 2, 0, 2, 3]);
+    }
+
+    #[test]
+    fn missing_normals_are_generated_from_the_face(){
+        let raw = parse(QUAD);
+        let (vertices, _) = build_geometry(&raw, &[0]).unwrap();
+        for vertex in &vertices {
+            assert_eq!(vertex.normal, [0.0, 0.0, 1.0]);
+            assert_eq!(vertex.uv, [0.0, 0.0]);
+        }
+    }
+
+    #[test]
+    fn uvs_are_flipped_and_given_normals_are_kept(){
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0.25
+vn 0 0 -1
+f 1/1 2/2 3/1
+f 1//1 3//1 2//1
+";
+        let raw = parse(source);
+        let (vertices, indices) = build_geometry(&raw, &[0, 1]).unwrap();
+        assert_eq!(indices.len(), 6);
+        assert_eq!(vertices[1].uv, [1.0, 0.75]);
+        assert_eq!(vertices[1].normal, [0.0, 0.0, 1.0]);
+        let normal_vertex = &vertices[indices[3] as usize];
+        assert_eq!(normal_vertex.uv, [0.0, 0.0]);
+        assert_eq!(normal_vertex.normal, [0.0, 0.0, -1.0]);
+    }
+
+    #[test]
+    fn objects_take_precedence_over_groups(){
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f 1 2 3
+o first
+g ignored
+f 1 2 3
+f 1 3 2
+o second
+usemtl material
+f 1 2 3
+";
+        assert_eq!(group_names(source), vec![
+            (String::new(), vec![0]),
+            (String::from("first"), vec![1, 2]),
+            (String::from("second"), vec![3])
+        ]);
+    }
+
+    #[test]
+    fn groups_are_used_without_objects(){
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+g first
+f 1 2 3
+g second
+f 1 3 2
+g first
+f 1 2 3
+";
+        assert_eq!(group_names(source), vec![
+            (String::from("first"), vec![0, 2]),
+            (String::from("second"), vec![1])
+        ]);
+    }
+
+    #[test]
+    fn ungrouped_faces_fall_back_to_the_default_group(){
+        assert_eq!(group_names(QUAD), vec![(String::from("default"), vec![0])]);
+    }
+
+    #[test]
+    fn continued_face_lines_count_once(){
+        let source = "
+v 0 0 0
+v 1 0 0
+v 0 1 0
+o split # comment
+f 1 2 \\
+  3
+f 1 3 2
+";
+        assert_eq!(group_names(source), vec![(String::from("split"), vec![0, 1])]);
+    }
+}
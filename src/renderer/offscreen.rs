@@ -0,0 +1,72 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::sync::Arc;
+use vulkano::buffer::{BufferUsage, CpuAccessibleBuffer};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage, ImageUsage};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use crate::renderer::error::RendererError;
+
+pub const OFFSCREEN_FORMAT: Format = Format::R8G8B8A8_SRGB;
+
+pub struct OffscreenTarget{
+    pub(crate) image: Arc<AttachmentImage>,
+    pub(crate) readback_buffer: Arc<CpuAccessibleBuffer<[u8]>>,
+    extent: [u32; 2]
+}
+
+impl OffscreenTarget {
+    pub(crate) fn new(allocator:&StandardMemoryAllocator, extent:[u32; 2]) -> Result<Self, RendererError>{
+        let image: Arc<AttachmentImage> = AttachmentImage::with_usage(
+            allocator,
+            extent,
+            OFFSCREEN_FORMAT,
+            ImageUsage {
+                color_attachment: true,
+                transfer_src: true,
+                ..ImageUsage::empty()
+            },
+        ).map_err(RendererError::allocation)?;
+
+        // Four bytes per pixel, computed in usize since the product overflows u32 for large targets.
+        let byte_count: usize = (extent[0] as usize).checked_mul(extent[1] as usize)
+            .and_then(|pixel_count| pixel_count.checked_mul(4))
+            .ok_or_else(|| RendererError::allocation(format!("a {}x{} readback buffer does not fit in memory", extent[0], extent[1])))?;
+        let readback_buffer: Arc<CpuAccessibleBuffer<[u8]>> = CpuAccessibleBuffer::from_iter(
+            allocator,
+            BufferUsage {
+                transfer_dst: true,
+                ..BufferUsage::empty()
+            },
+            true,
+            (0..byte_count).map(|_| 0u8),
+        ).map_err(RendererError::allocation)?;
+
+        return Ok(Self{
+            image: image,
+            readback_buffer: readback_buffer,
+            extent: extent
+        });
+    }
+
+    pub fn extent(&self) -> [u32; 2]{
+        return self.extent;
+    }
+
+    pub fn pixels(&self) -> Result<Vec<u8>, RendererError>{
+        let pixels = self.readback_buffer.read().map_err(RendererError::readback)?;
+        return Ok(pixels.to_vec());
+    }
+
+    pub fn save_png(&self, path:&Path) -> Result<(), RendererError>{
+        let pixels = self.pixels()?;
+        let file = File::create(path).map_err(RendererError::readback)?;
+        let mut encoder = png::Encoder::new(BufWriter::new(file), self.extent[0], self.extent[1]);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header().map_err(RendererError::readback)?;
+        writer.write_image_data(&pixels).map_err(RendererError::readback)?;
+        return Ok(());
+    }
+}
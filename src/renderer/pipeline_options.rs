@@ -0,0 +1,38 @@
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PipelineOptions{
+    pub depth_test: bool,
+    pub depth_write: bool,
+    // Alpha blended, and drawn back-to-front after the opaque geometry of the same layer.
+    pub transparent: bool
+}
+
+impl Default for PipelineOptions {
+    fn default() -> Self{
+        return Self{
+            depth_test: true,
+            depth_write: true,
+            transparent: false
+        };
+    }
+}
+
+impl PipelineOptions {
+    pub fn depth_test(mut self, depth_test:bool) -> Self{
+        self.depth_test = depth_test;
+        return self;
+    }
+
+    pub fn depth_write(mut self, depth_write:bool) -> Self{
+        self.depth_write = depth_write;
+        return self;
+    }
+
+    // Transparent surfaces still test against depth but stop writing it, so they don't hide each other.
+    pub fn transparent(mut self, transparent:bool) -> Self{
+        self.transparent = transparent;
+        if transparent {
+            self.depth_write = false;
+        }
+        return self;
+    }
+}
\ No newline at end of file
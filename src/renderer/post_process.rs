@@ -0,0 +1,754 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+use bytemuck::{Pod, Zeroable};
+use vulkano::command_buffer::{AutoCommandBufferBuilder, PrimaryAutoCommandBuffer, RenderPassBeginInfo, SubpassContents};
+use vulkano::descriptor_set::allocator::StandardDescriptorSetAllocator;
+use vulkano::descriptor_set::{PersistentDescriptorSet, WriteDescriptorSet};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::format::Format;
+use vulkano::image::{AttachmentImage, ImageAccess, ImageUsage};
+use vulkano::image::view::ImageView;
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::color_blend::ColorBlendState;
+use vulkano::pipeline::graphics::input_assembly::InputAssemblyState;
+use vulkano::pipeline::graphics::vertex_input::BuffersDefinition;
+use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
+use vulkano::pipeline::{GraphicsPipeline, Pipeline, PipelineBindPoint};
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
+use vulkano::sampler::{Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use crate::renderer::debug::set_debug_name;
+use crate::renderer::error::RendererError;
+use crate::renderer::shader_loader::ShaderContainer;
+use crate::renderer::ShaderType;
+
+// Scene format when HDR is enabled. Lighting can exceed 1.0 until tone mapping brings it into the target's range.
+pub const HDR_FORMAT: Format = Format::R16G16B16A16_SFLOAT;
+
+// Eye adaptation averages the log luminance of the scene downsampled to this size, halving it down to 1x1.
+const LUMINANCE_SIZE: u32 = 64;
+const LUMINANCE_FORMAT: Format = Format::R16_SFLOAT;
+
+// Every effect is drawn by this vertex shader, which hands `v_uv` to the fragment shader at location 0.
+pub const POST_VERTEX_SHADER: &str = "post";
+
+const COPY_SHADER: &str = "post_copy";
+const TONE_MAPPING_SHADER: &str = "post_tone_mapping";
+const BLOOM_THRESHOLD_SHADER: &str = "post_bloom_threshold";
+const BLOOM_BLUR_SHADER: &str = "post_bloom_blur";
+const BLOOM_COMPOSITE_SHADER: &str = "post_bloom_composite";
+const FXAA_SHADER: &str = "post_fxaa";
+const VIGNETTE_SHADER: &str = "post_vignette";
+const LUMINANCE_SHADER: &str = "post_luminance";
+const EYE_ADAPTATION_SHADER: &str = "post_eye_adaptation";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMapOperator{
+    Reinhard,
+    // Narkowicz's fit of the ACES filmic curve.
+    Aces
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Exposure{
+    // Scene colors are multiplied by this before tone mapping.
+    Manual(f32),
+    // Exposure follows the average scene luminance, adjusting gradually when it changes.
+    Automatic(EyeAdaptation)
+}
+
+impl Default for Exposure {
+    fn default() -> Self{
+        return Exposure::Manual(1.0);
+    }
+}
+
+impl Exposure {
+    pub fn automatic() -> Self{
+        return Exposure::Automatic(EyeAdaptation::default());
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct EyeAdaptation{
+    // The adapted average luminance is exposed to this value, 0.18 being middle grey.
+    pub key_value: f32,
+    // The adapted luminance stays in this range, limiting the exposure in very dark or very bright scenes.
+    pub min_luminance: f32,
+    pub max_luminance: f32,
+    // Adaptation rates per second towards a brighter and towards a darker scene.
+    pub speed_up: f32,
+    pub speed_down: f32
+}
+
+impl Default for EyeAdaptation {
+    fn default() -> Self{
+        return Self{
+            key_value: 0.18,
+            min_luminance: 0.03,
+            max_luminance: 8.0,
+            speed_up: 3.0,
+            speed_down: 1.0
+        };
+    }
+}
+
+impl EyeAdaptation {
+    pub fn key_value(mut self, key_value:f32) -> Self{
+        self.key_value = key_value;
+        return self;
+    }
+
+    pub fn luminance_range(mut self, min_luminance:f32, max_luminance:f32) -> Self{
+        self.min_luminance = min_luminance;
+        self.max_luminance = max_luminance.max(min_luminance);
+        return self;
+    }
+
+    pub fn speed(mut self, speed_up:f32, speed_down:f32) -> Self{
+        self.speed_up = speed_up;
+        self.speed_down = speed_down;
+        return self;
+    }
+}
+
+// Fragment shaders sample the previous effect's output from `sampler2D source` at set 0 binding 0 and get
+// `PostParameters { vec4 parameters; vec2 texel_size; }` as push constants, `texel_size` being one over the source size.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PostEffect{
+    // Applies the renderer's exposure, then maps colors into the 0..1 range.
+    ToneMapping{ operator: ToneMapOperator },
+    // Pixels brighter than `threshold` are blurred at half resolution and added back scaled by `intensity`.
+    Bloom{ threshold: f32, intensity: f32 },
+    // Works best after tone mapping, on colors in the 0..1 range.
+    Fxaa,
+    // Darkens towards the corners, starting `radius` from the centre and reaching full `intensity` `smoothness` later.
+    Vignette{ intensity: f32, radius: f32, smoothness: f32 },
+    // A fragment shader registered under `fragment_shader`, e.g. through `Renderer::load_shader`. `parameters` are passed as is.
+    Custom{ fragment_shader: String, parameters: [f32; 4] }
+}
+
+impl PostEffect {
+    pub fn tone_mapping() -> Self{
+        return PostEffect::ToneMapping{ operator: ToneMapOperator::Aces };
+    }
+
+    pub fn bloom() -> Self{
+        return PostEffect::Bloom{ threshold: 1.0, intensity: 0.5 };
+    }
+
+    pub fn vignette() -> Self{
+        return PostEffect::Vignette{ intensity: 0.5, radius: 0.6, smoothness: 0.5 };
+    }
+
+    pub fn custom(fragment_shader:&str, parameters:[f32; 4]) -> Self{
+        return PostEffect::Custom{ fragment_shader: String::from(fragment_shader), parameters: parameters };
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct EffectId(u64);
+
+struct ChainEntry{
+    id: EffectId,
+    effect: PostEffect,
+    enabled: bool
+}
+
+// Effects run in order on the rendered scene, each one reading the previous one's output. The last enabled effect
+// writes to the swapchain or offscreen image; with none enabled the scene is copied there unchanged. An HDR scene
+// without an enabled tone mapping effect is tone mapped after the last one.
+#[derive(Default)]
+pub struct PostProcessChain{
+    entries: Vec<ChainEntry>,
+    next_id: u64
+}
+
+impl PostProcessChain {
+    pub fn new() -> Self{
+        return Self::default();
+    }
+
+    // Appends an enabled effect to the end of the chain.
+    pub fn push(&mut self, effect:PostEffect) -> EffectId{
+        return self.insert(self.entries.len(), effect);
+    }
+
+    // `index` is clamped to the length of the chain.
+    pub fn insert(&mut self, index:usize, effect:PostEffect) -> EffectId{
+        let id = EffectId(self.next_id);
+        self.next_id += 1;
+        self.entries.insert(index.min(self.entries.len()), ChainEntry{
+            id: id,
+            effect: effect,
+            enabled: true
+        });
+        return id;
+    }
+
+    pub fn remove(&mut self, id:EffectId) -> Option<PostEffect>{
+        let index = self.position(id)?;
+        return Some(self.entries.remove(index).effect);
+    }
+
+    pub fn clear(&mut self){
+        self.entries.clear();
+    }
+
+    pub fn position(&self, id:EffectId) -> Option<usize>{
+        return self.entries.iter().position(|entry| entry.id == id);
+    }
+
+    // Moves the effect so it runs at `index`, clamped to the last position.
+    pub fn move_to(&mut self, id:EffectId, index:usize) -> Option<()>{
+        let entry = self.entries.remove(self.position(id)?);
+        self.entries.insert(index.min(self.entries.len()), entry);
+        return Some(());
+    }
+
+    pub fn set_enabled(&mut self, id:EffectId, enabled:bool) -> Option<()>{
+        let index = self.position(id)?;
+        self.entries[index].enabled = enabled;
+        return Some(());
+    }
+
+    pub fn is_enabled(&self, id:EffectId) -> Option<bool>{
+        return self.position(id).map(|index| self.entries[index].enabled);
+    }
+
+    pub fn effect(&self, id:EffectId) -> Option<&PostEffect>{
+        return self.position(id).map(|index| &self.entries[index].effect);
+    }
+
+    // Parameters can be changed in place, they are read again every frame.
+    pub fn effect_mut(&mut self, id:EffectId) -> Option<&mut PostEffect>{
+        let index = self.position(id)?;
+        return Some(&mut self.entries[index].effect);
+    }
+
+    // In the order the effects run, disabled ones included.
+    pub fn ids(&self) -> Vec<EffectId>{
+        return self.entries.iter().map(|entry| entry.id).collect();
+    }
+
+    pub fn len(&self) -> usize{
+        return self.entries.len();
+    }
+
+    pub fn is_empty(&self) -> bool{
+        return self.entries.is_empty();
+    }
+
+    fn enabled_effects(&self) -> Vec<&PostEffect>{
+        return self.entries.iter()
+            .filter(|entry| entry.enabled)
+            .map(|entry| &entry.effect)
+            .collect();
+    }
+}
+
+// Matches the `PostParameters` push constant block of the effect shaders.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+struct PostParameters{
+    parameters: [f32; 4],
+    texel_size: [f32; 2]
+}
+
+// Intermediate passes write the scene format, the last pass writes the target format.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum PostOutput{
+    Intermediate,
+    Target,
+    Luminance
+}
+
+struct CachedPipeline{
+    fragment_generation: u64,
+    pipeline: Arc<GraphicsPipeline>
+}
+
+// Lives for one window size, recreated together with the framebuffers.
+struct PostTargets{
+    scene: Arc<ImageView<AttachmentImage>>,
+    // Effects alternate between these so no pass reads the image it writes.
+    ping_pong: [(Arc<ImageView<AttachmentImage>>, Arc<Framebuffer>); 2],
+    // Half resolution, bloom blurs back and forth between them.
+    bloom: [(Arc<ImageView<AttachmentImage>>, Arc<Framebuffer>); 2],
+    // One per swapchain image, or the offscreen image.
+    target_framebuffers: Vec<Arc<Framebuffer>>
+}
+
+// Independent of the window size, created once.
+struct LuminanceTargets{
+    // Log luminance from `LUMINANCE_SIZE` down to 1x1.
+    levels: Vec<(Arc<ImageView<AttachmentImage>>, Arc<Framebuffer>)>,
+    // 1x1 adapted luminance, written from the other one every frame.
+    adapted: [(Arc<ImageView<AttachmentImage>>, Arc<Framebuffer>); 2]
+}
+
+pub(crate) struct PostProcessor{
+    device: Arc<Device>,
+    scene_format: Format,
+    intermediate_render_pass: Arc<RenderPass>,
+    target_render_pass: Arc<RenderPass>,
+    luminance_render_pass: Arc<RenderPass>,
+    sampler: Arc<Sampler>,
+    pipelines: HashMap<(String, PostOutput), CachedPipeline>,
+    targets: Option<PostTargets>,
+    luminance: Option<LuminanceTargets>,
+    // Forces tone mapping into the target.
+    hdr: bool,
+    exposure: Exposure,
+    // Which `LuminanceTargets::adapted` image holds the latest adapted luminance.
+    adapted_index: usize,
+    // Set until eye adaptation has run once, so it starts from the measured luminance instead of an old value.
+    adaptation_reset: bool,
+    last_adaptation: Option<Instant>
+}
+
+impl PostProcessor {
+    pub(crate) fn new(device:Arc<Device>, hdr:bool, target_format:Format, exposure:Exposure) -> Result<Self, RendererError>{
+        let scene_format = if hdr { HDR_FORMAT } else { target_format };
+        let sampler = Sampler::new(device.clone(), SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode: [SamplerAddressMode::ClampToEdge; 3],
+            ..Default::default()
+        }).map_err(RendererError::allocation)?;
+
+        return Ok(Self{
+            device: device.clone(),
+            scene_format: scene_format,
+            intermediate_render_pass: create_post_render_pass(device.clone(), scene_format)?,
+            target_render_pass: create_post_render_pass(device.clone(), target_format)?,
+            luminance_render_pass: create_post_render_pass(device, LUMINANCE_FORMAT)?,
+            sampler: sampler,
+            pipelines: HashMap::new(),
+            targets: None,
+            luminance: None,
+            hdr: hdr,
+            exposure: exposure,
+            adapted_index: 0,
+            adaptation_reset: true,
+            last_adaptation: None
+        });
+    }
+
+    pub(crate) fn exposure(&self) -> Exposure{
+        return self.exposure;
+    }
+
+    pub(crate) fn set_exposure(&mut self, exposure:Exposure){
+        self.exposure = exposure;
+    }
+
+    pub(crate) fn scene_format(&self) -> Format{
+        return self.scene_format;
+    }
+
+    // Recreates the intermediate images for the size of `images` and returns the image the scene is rendered into.
+    pub(crate) fn resize<I: ImageAccess + 'static>(&mut self, allocator:&StandardMemoryAllocator, images:&[Arc<I>]) -> Result<Arc<ImageView<AttachmentImage>>, RendererError>{
+        let dimensions = images[0].dimensions().width_height();
+        let bloom_dimensions = [(dimensions[0] / 2).max(1), (dimensions[1] / 2).max(1)];
+
+        let (intermediate_render_pass, luminance_render_pass, scene_format) =
+            (self.intermediate_render_pass.clone(), self.luminance_render_pass.clone(), self.scene_format);
+        let intermediate = |dimensions:[u32; 2], name:&str| create_render_target(allocator, &intermediate_render_pass, dimensions, scene_format, name);
+        let luminance = |dimensions:[u32; 2], name:&str| create_render_target(allocator, &luminance_render_pass, dimensions, LUMINANCE_FORMAT, name);
+
+        if self.luminance.is_none() {
+            let mut levels = Vec::new();
+            let mut size = LUMINANCE_SIZE;
+            loop {
+                levels.push(luminance([size, size], &format!("luminance {}x{}", size, size))?);
+                if size == 1 {
+                    break;
+                }
+                size /= 2;
+            }
+            self.luminance = Some(LuminanceTargets{
+                levels: levels,
+                adapted: [luminance([1, 1], "adapted luminance ping")?, luminance([1, 1], "adapted luminance pong")?]
+            });
+        }
+
+        let target_framebuffers = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| {
+                set_debug_name(allocator.device(), image.inner().image.as_ref(), &format!("color target {}", index));
+                let view = ImageView::new_default(image.clone()).map_err(RendererError::allocation)?;
+                Framebuffer::new(
+                    self.target_render_pass.clone(),
+                    FramebufferCreateInfo {
+                        attachments: vec![view],
+                        ..Default::default()
+                    },
+                )
+                    .map_err(RendererError::allocation)
+            })
+            .collect::<Result<Vec<_>, RendererError>>()?;
+
+        let scene = create_intermediate_image(allocator, dimensions, self.scene_format, "scene color")?;
+        self.targets = Some(PostTargets{
+            scene: scene.clone(),
+            ping_pong: [intermediate(dimensions, "post-process ping")?, intermediate(dimensions, "post-process pong")?],
+            bloom: [intermediate(bloom_dimensions, "bloom ping")?, intermediate(bloom_dimensions, "bloom pong")?],
+            target_framebuffers: target_framebuffers
+        });
+        return Ok(scene);
+    }
+
+    // Records the enabled effects of `chain` after the scene render pass, ending in the target framebuffer at `target_index`.
+    pub(crate) fn record(&mut self, command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, chain:&PostProcessChain, shader_container:&ShaderContainer, descriptor_set_allocator:&StandardDescriptorSetAllocator, target_index:usize) -> Result<(), RendererError>{
+        let mut effects = chain.enabled_effects();
+        let copy = PostEffect::custom(COPY_SHADER, [0.0; 4]);
+        let tone_mapping = PostEffect::tone_mapping();
+        let mut tone_mapped = effects.iter().any(|effect| matches!(effect, PostEffect::ToneMapping{ .. }));
+        if self.hdr && !tone_mapped {
+            effects.push(&tone_mapping);
+            tone_mapped = true;
+        }
+        if effects.is_empty() {
+            effects.push(&copy);
+        }
+
+        let targets = self.targets.as_ref()
+            .ok_or_else(|| RendererError::allocation("post-processing targets were never created"))?;
+        let scene = targets.scene.clone();
+        let ping_pong = targets.ping_pong.clone();
+        let bloom = targets.bloom.clone();
+        let target_framebuffer = targets.target_framebuffers[target_index].clone();
+
+        let adapted_luminance = self.record_eye_adaptation(command_buffer_builder, shader_container, descriptor_set_allocator, scene.clone(), tone_mapped)?;
+
+        let mut source = scene;
+        for (index, effect) in effects.iter().enumerate() {
+            let (output, framebuffer) = if index + 1 == effects.len() {
+                (PostOutput::Target, target_framebuffer.clone())
+            } else {
+                (PostOutput::Intermediate, ping_pong[index % 2].1.clone())
+            };
+
+            match effect {
+                PostEffect::Bloom{ threshold, intensity } => {
+                    let threshold_pipeline = self.pipeline(shader_container, BLOOM_THRESHOLD_SHADER, PostOutput::Intermediate)?;
+                    self.record_pass(command_buffer_builder, descriptor_set_allocator, threshold_pipeline, bloom[0].1.clone(), &[source.clone()], [*threshold, 0.0, 0.0, 0.0])?;
+                    // Separable blur, horizontal then vertical.
+                    let blur_pipeline = self.pipeline(shader_container, BLOOM_BLUR_SHADER, PostOutput::Intermediate)?;
+                    self.record_pass(command_buffer_builder, descriptor_set_allocator, blur_pipeline.clone(), bloom[1].1.clone(), &[bloom[0].0.clone()], [1.0, 0.0, 0.0, 0.0])?;
+                    self.record_pass(command_buffer_builder, descriptor_set_allocator, blur_pipeline, bloom[0].1.clone(), &[bloom[1].0.clone()], [0.0, 1.0, 0.0, 0.0])?;
+                    let composite_pipeline = self.pipeline(shader_container, BLOOM_COMPOSITE_SHADER, output)?;
+                    self.record_pass(command_buffer_builder, descriptor_set_allocator, composite_pipeline, framebuffer, &[source.clone(), bloom[0].0.clone()], [*intensity, 0.0, 0.0, 0.0])?;
+                }
+                PostEffect::ToneMapping{ .. } => {
+                    let (fragment_shader, mut parameters) = effect_shader(effect);
+                    match self.exposure {
+                        Exposure::Manual(exposure) => parameters[1] = exposure,
+                        Exposure::Automatic(adaptation) => {
+                            parameters[1] = adaptation.key_value;
+                            parameters[2] = 1.0;
+                        }
+                    }
+                    let pipeline = self.pipeline(shader_container, fragment_shader, output)?;
+                    self.record_pass(command_buffer_builder, descriptor_set_allocator, pipeline, framebuffer, &[source.clone(), adapted_luminance.clone()], parameters)?;
+                }
+                _ => {
+                    let (fragment_shader, parameters) = effect_shader(effect);
+                    let pipeline = self.pipeline(shader_container, fragment_shader, output)?;
+                    self.record_pass(command_buffer_builder, descriptor_set_allocator, pipeline, framebuffer, &[source.clone()], parameters)?;
+                }
+            }
+            source = ping_pong[index % 2].0.clone();
+        }
+        return Ok(());
+    }
+
+    // Measures the average scene luminance and moves the adapted luminance towards it. Returns the image tone mapping
+    // reads the adapted luminance from, which is left untouched with manual exposure or when nothing is tone mapped.
+    fn record_eye_adaptation(&mut self, command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, shader_container:&ShaderContainer, descriptor_set_allocator:&StandardDescriptorSetAllocator, scene:Arc<ImageView<AttachmentImage>>, tone_mapped:bool) -> Result<Arc<ImageView<AttachmentImage>>, RendererError>{
+        let luminance = self.luminance.as_ref()
+            .ok_or_else(|| RendererError::allocation("luminance targets were never created"))?;
+        let levels = luminance.levels.clone();
+        let adapted = luminance.adapted.clone();
+
+        let adaptation = match self.exposure {
+            Exposure::Automatic(adaptation) if tone_mapped => adaptation,
+            _ => {
+                // Adaptation starts over from the measured luminance once it runs again.
+                self.last_adaptation = None;
+                self.adaptation_reset = true;
+                return Ok(adapted[self.adapted_index].0.clone());
+            }
+        };
+
+        let luminance_pipeline = self.pipeline(shader_container, LUMINANCE_SHADER, PostOutput::Luminance)?;
+        self.record_pass(command_buffer_builder, descriptor_set_allocator, luminance_pipeline, levels[0].1.clone(), &[scene], [0.0; 4])?;
+        // Each level samples between four texels of the previous one, so the linear filter averages them.
+        let downsample_pipeline = self.pipeline(shader_container, COPY_SHADER, PostOutput::Luminance)?;
+        for level in 1..levels.len() {
+            self.record_pass(command_buffer_builder, descriptor_set_allocator, downsample_pipeline.clone(), levels[level].1.clone(), &[levels[level - 1].0.clone()], [0.0; 4])?;
+        }
+
+        let now = Instant::now();
+        let delta_time = self.last_adaptation.map_or(0.0, |last| (now - last).as_secs_f32());
+        self.last_adaptation = Some(now);
+        // Fractions of the way to the measured luminance covered this frame, negative to skip the previous value.
+        let (rate_up, rate_down) = if self.adaptation_reset {
+            (-1.0, -1.0)
+        } else {
+            (1.0 - (-delta_time * adaptation.speed_up).exp(), 1.0 - (-delta_time * adaptation.speed_down).exp())
+        };
+        self.adaptation_reset = false;
+
+        let write_index = 1 - self.adapted_index;
+        let adaptation_pipeline = self.pipeline(shader_container, EYE_ADAPTATION_SHADER, PostOutput::Luminance)?;
+        self.record_pass(
+            command_buffer_builder,
+            descriptor_set_allocator,
+            adaptation_pipeline,
+            adapted[write_index].1.clone(),
+            &[levels[levels.len() - 1].0.clone(), adapted[self.adapted_index].0.clone()],
+            [rate_up, rate_down, adaptation.min_luminance, adaptation.max_luminance],
+        )?;
+        self.adapted_index = write_index;
+        return Ok(adapted[write_index].0.clone());
+    }
+
+    fn record_pass(&self, command_buffer_builder:&mut AutoCommandBufferBuilder<PrimaryAutoCommandBuffer>, descriptor_set_allocator:&StandardDescriptorSetAllocator, pipeline:Arc<GraphicsPipeline>, framebuffer:Arc<Framebuffer>, inputs:&[Arc<ImageView<AttachmentImage>>], parameters:[f32; 4]) -> Result<(), RendererError>{
+        let layout = pipeline.layout().set_layouts()
+            .get(0)
+            .ok_or_else(|| RendererError::pipeline("post-processing shader declares no source texture in set 0"))?
+            .clone();
+        // Inputs the shader does not declare are left out, e.g. the bloom texture for a plain effect.
+        let writes = inputs.iter()
+            .enumerate()
+            .filter(|(binding, _)| layout.bindings().contains_key(&(*binding as u32)))
+            .map(|(binding, input)| WriteDescriptorSet::image_view_sampler(binding as u32, input.clone(), self.sampler.clone()))
+            .collect::<Vec<_>>();
+        let descriptor_set = PersistentDescriptorSet::new(descriptor_set_allocator, layout, writes)
+            .map_err(RendererError::allocation)?;
+
+        let source_dimensions = inputs[0].image().dimensions().width_height();
+        let target_dimensions = framebuffer.extent();
+
+        command_buffer_builder
+            .begin_render_pass(
+                RenderPassBeginInfo {
+                    clear_values: vec![None],
+                    ..RenderPassBeginInfo::framebuffer(framebuffer)
+                },
+                SubpassContents::Inline,
+            ).map_err(RendererError::submission)?
+            .set_viewport(0, [Viewport {
+                origin: [0.0, 0.0],
+                dimensions: [target_dimensions[0] as f32, target_dimensions[1] as f32],
+                depth_range: 0.0..1.0,
+            }])
+            .bind_pipeline_graphics(pipeline.clone())
+            .bind_descriptor_sets(PipelineBindPoint::Graphics, pipeline.layout().clone(), 0, descriptor_set);
+
+        if !pipeline.layout().push_constant_ranges().is_empty() {
+            command_buffer_builder.push_constants(pipeline.layout().clone(), 0, PostParameters{
+                parameters: parameters,
+                texel_size: [1.0 / source_dimensions[0] as f32, 1.0 / source_dimensions[1] as f32]
+            });
+        }
+
+        command_buffer_builder
+            .draw(3, 1, 0, 0).map_err(RendererError::submission)?
+            .end_render_pass().map_err(RendererError::submission)?;
+        return Ok(());
+    }
+
+    // Pipelines are built on first use and rebuilt when their fragment shader is reloaded.
+    fn pipeline(&mut self, shader_container:&ShaderContainer, fragment_shader:&str, output:PostOutput) -> Result<Arc<GraphicsPipeline>, RendererError>{
+        let generation = shader_container.generation(ShaderType::Fragment, fragment_shader)
+            .ok_or_else(|| RendererError::ShaderNotFound{
+                shader_type: ShaderType::Fragment,
+                name: String::from(fragment_shader)
+            })?;
+        let key = (String::from(fragment_shader), output);
+        if let Some(cached) = self.pipelines.get(&key) {
+            if cached.fragment_generation == generation {
+                return Ok(cached.pipeline.clone());
+            }
+        }
+
+        let not_found = |shader_type:ShaderType, name:&str| RendererError::ShaderNotFound{
+            shader_type: shader_type,
+            name: String::from(name)
+        };
+        let vertex_shader = shader_container.get_shader(ShaderType::Vertex, POST_VERTEX_SHADER)
+            .ok_or_else(|| not_found(ShaderType::Vertex, POST_VERTEX_SHADER))?;
+        let fragment_module = shader_container.get_shader(ShaderType::Fragment, fragment_shader)
+            .ok_or_else(|| not_found(ShaderType::Fragment, fragment_shader))?;
+
+        let vertex_entry_point = vertex_shader.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("vertex shader has no `main` entry point"))?;
+        let fragment_entry_point = fragment_module.entry_point("main")
+            .ok_or_else(|| RendererError::pipeline("fragment shader has no `main` entry point"))?;
+
+        let render_pass = match output {
+            PostOutput::Intermediate => self.intermediate_render_pass.clone(),
+            PostOutput::Target => self.target_render_pass.clone(),
+            PostOutput::Luminance => self.luminance_render_pass.clone()
+        };
+        let subpass = Subpass::from(render_pass, 0)
+            .ok_or_else(|| RendererError::pipeline("post-processing render pass has no subpass"))?;
+
+        let pipeline = GraphicsPipeline::start()
+            .render_pass(subpass)
+            .vertex_input_state(BuffersDefinition::new())
+            .input_assembly_state(InputAssemblyState::new())
+            .vertex_shader(vertex_entry_point, ())
+            .fragment_shader(fragment_entry_point, ())
+            .viewport_state(ViewportState::viewport_dynamic_scissor_irrelevant())
+            .color_blend_state(ColorBlendState::new(1))
+            .build(self.device.clone())
+            .map_err(RendererError::pipeline)?;
+        set_debug_name(&self.device, pipeline.as_ref(), &format!("post-processing pipeline {}", fragment_shader));
+
+        self.pipelines.insert(key, CachedPipeline{
+            fragment_generation: generation,
+            pipeline: pipeline.clone()
+        });
+        return Ok(pipeline);
+    }
+}
+
+// Fragment shader and push constant parameters of the single-pass effects.
+fn effect_shader(effect:&PostEffect) -> (&str, [f32; 4]){
+    return match effect {
+        PostEffect::ToneMapping{ operator } => {
+            let operator = match operator {
+                ToneMapOperator::Reinhard => 0.0,
+                ToneMapOperator::Aces => 1.0
+            };
+            (TONE_MAPPING_SHADER, [operator, 0.0, 0.0, 0.0])
+        }
+        PostEffect::Bloom{ intensity, .. } => (BLOOM_COMPOSITE_SHADER, [*intensity, 0.0, 0.0, 0.0]),
+        PostEffect::Fxaa => (FXAA_SHADER, [0.0; 4]),
+        PostEffect::Vignette{ intensity, radius, smoothness } => (VIGNETTE_SHADER, [*intensity, *radius, *smoothness, 0.0]),
+        PostEffect::Custom{ fragment_shader, parameters } => (fragment_shader.as_str(), *parameters)
+    };
+}
+
+fn create_intermediate_image(allocator:&StandardMemoryAllocator, dimensions:[u32; 2], format:Format, name:&str) -> Result<Arc<ImageView<AttachmentImage>>, RendererError>{
+    let image = AttachmentImage::with_usage(
+        allocator,
+        dimensions,
+        format,
+        ImageUsage {
+            color_attachment: true,
+            sampled: true,
+            ..ImageUsage::empty()
+        },
+    ).map_err(RendererError::allocation)?;
+    set_debug_name(allocator.device(), image.inner().image.as_ref(), name);
+    return ImageView::new_default(image).map_err(RendererError::allocation);
+}
+
+fn create_render_target(allocator:&StandardMemoryAllocator, render_pass:&Arc<RenderPass>, dimensions:[u32; 2], format:Format, name:&str) -> Result<(Arc<ImageView<AttachmentImage>>, Arc<Framebuffer>), RendererError>{
+    let view = create_intermediate_image(allocator, dimensions, format, name)?;
+    let framebuffer = Framebuffer::new(
+        render_pass.clone(),
+        FramebufferCreateInfo {
+            attachments: vec![view.clone()],
+            ..Default::default()
+        },
+    ).map_err(RendererError::allocation)?;
+    return Ok((view, framebuffer));
+}
+
+// Every pass overwrites the whole attachment, so nothing is loaded.
+fn create_post_render_pass(device:Arc<Device>, format:Format) -> Result<Arc<RenderPass>, RendererError>{
+    return vulkano::single_pass_renderpass!(
+        device,
+        attachments: {
+            color: {
+                load: DontCare,
+                store: Store,
+                format: format,
+                samples: 1,
+            }
+        },
+        pass: {
+            color: [color],
+            depth_stencil: {}
+        }).map_err(RendererError::pipeline);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_appends_and_insert_clamps(){
+        let mut chain = PostProcessChain::new();
+        let bloom = chain.push(PostEffect::bloom());
+        let tone_mapping = chain.push(PostEffect::tone_mapping());
+        let vignette = chain.insert(1, PostEffect::vignette());
+        let fxaa = chain.insert(100, PostEffect::Fxaa);
+        assert_eq!(chain.ids(), vec![bloom, vignette, tone_mapping, fxaa]);
+        assert_eq!(chain.len(), 4);
+    }
+
+    #[test]
+    fn move_to_reorders(){
+        let mut chain = PostProcessChain::new();
+        let a = chain.push(PostEffect::bloom());
+        let b = chain.push(PostEffect::tone_mapping());
+        let c = chain.push(PostEffect::Fxaa);
+
+        chain.move_to(c, 0).unwrap();
+        assert_eq!(chain.ids(), vec![c, a, b]);
+        chain.move_to(c, 100).unwrap();
+        assert_eq!(chain.ids(), vec![a, b, c]);
+        chain.move_to(a, 1).unwrap();
+        assert_eq!(chain.ids(), vec![b, a, c]);
+        assert_eq!(chain.position(a), Some(1));
+    }
+
+    #[test]
+    fn disabled_effects_keep_their_place(){
+        let mut chain = PostProcessChain::new();
+        let bloom = chain.push(PostEffect::bloom());
+        let tone_mapping = chain.push(PostEffect::tone_mapping());
+        let fxaa = chain.push(PostEffect::Fxaa);
+
+        chain.set_enabled(tone_mapping, false).unwrap();
+        assert_eq!(chain.is_enabled(tone_mapping), Some(false));
+        assert_eq!(chain.ids(), vec![bloom, tone_mapping, fxaa]);
+        assert_eq!(chain.enabled_effects(), vec![&PostEffect::bloom(), &PostEffect::Fxaa]);
+
+        chain.set_enabled(tone_mapping, true).unwrap();
+        assert_eq!(chain.enabled_effects(), vec![&PostEffect::bloom(), &PostEffect::tone_mapping(), &PostEffect::Fxaa]);
+    }
+
+    #[test]
+    fn removed_ids_are_not_reused(){
+        let mut chain = PostProcessChain::new();
+        let bloom = chain.push(PostEffect::bloom());
+        assert_eq!(chain.remove(bloom), Some(PostEffect::bloom()));
+        assert!(chain.is_empty());
+
+        let fxaa = chain.push(PostEffect::Fxaa);
+        assert_ne!(bloom, fxaa);
+        assert!(chain.remove(bloom).is_none());
+        assert!(chain.move_to(bloom, 0).is_none());
+        assert!(chain.set_enabled(bloom, false).is_none());
+        assert!(chain.effect(bloom).is_none());
+    }
+
+    #[test]
+    fn effects_can_be_edited_in_place(){
+        let mut chain = PostProcessChain::new();
+        let vignette = chain.push(PostEffect::vignette());
+        if let Some(PostEffect::Vignette{ intensity, .. }) = chain.effect_mut(vignette) {
+            *intensity = 1.0;
+        }
+        assert_eq!(chain.effect(vignette), Some(&PostEffect::Vignette{ intensity: 1.0, radius: 0.6, smoothness: 0.5 }));
+    }
+}
@@ -0,0 +1,127 @@
+use std::sync::Arc;
+use nalgebra_glm as glm;
+use nalgebra_glm::Vec3;
+use crate::renderer::draw_call::DrawCall;
+
+// Counted while recording a frame. A bind is "saved" when the same object was already bound by the previous draw.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrameStatistics{
+    // Draw calls that were recorded, after culling.
+    pub draw_calls: u32,
+    // Draw calls rejected by frustum culling.
+    pub culled: u32,
+    // Depth-only draws recorded into the shadow maps, summed over all shadow casting lights.
+    pub shadow_draw_calls: u32,
+    pub pipeline_binds: u32,
+    pub pipeline_binds_saved: u32,
+    pub vertex_buffer_binds: u32,
+    pub vertex_buffer_binds_saved: u32,
+    pub index_buffer_binds: u32,
+    pub index_buffer_binds_saved: u32,
+    pub descriptor_set_binds: u32,
+    pub descriptor_set_binds_saved: u32
+}
+
+impl FrameStatistics {
+    pub fn binds_saved(&self) -> u32{
+        return self.pipeline_binds_saved
+            + self.vertex_buffer_binds_saved
+            + self.index_buffer_binds_saved
+            + self.descriptor_set_binds_saved;
+    }
+}
+
+// Field order is the sort order. Opaque geometry groups by state and then goes front-to-back inside each group,
+// transparent geometry is ordered strictly back-to-front so blending composes correctly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct SortKey{
+    layer: u8,
+    transparent: bool,
+    back_to_front: u32,
+    pipeline: usize,
+    material: usize,
+    model: usize,
+    front_to_back: u32
+}
+
+pub(crate) fn sort_draw_calls(draw_calls:&mut [DrawCall], camera_position:&Vec3, sort_by_depth:bool){
+    draw_calls.sort_by_cached_key(|draw_call| sort_key(draw_call, camera_position, sort_by_depth));
+}
+
+fn sort_key(draw_call:&DrawCall, camera_position:&Vec3, sort_by_depth:bool) -> SortKey{
+    let depth: u32 = if sort_by_depth { view_depth(draw_call, camera_position) } else { 0 };
+    return SortKey::new(
+        draw_call.layer,
+        draw_call.material.is_transparent(),
+        depth,
+        Arc::as_ptr(&draw_call.material.pipeline()) as *const () as usize,
+        draw_call.material.id(),
+        Arc::as_ptr(&draw_call.model.buffer) as *const () as usize);
+}
+
+impl SortKey {
+    fn new(layer:u8, transparent:bool, depth:u32, pipeline:usize, material:usize, model:usize) -> Self{
+        return Self{
+            layer: layer,
+            transparent: transparent,
+            back_to_front: if transparent { u32::MAX - depth } else { 0 },
+            pipeline: pipeline,
+            material: material,
+            model: model,
+            front_to_back: if transparent { 0 } else { depth }
+        };
+    }
+}
+
+// Squared distance from the camera to the draw call's origin. Non-negative floats keep their order as raw bits.
+fn view_depth(draw_call:&DrawCall, camera_position:&Vec3) -> u32{
+    let origin: Vec3 = Vec3::new(draw_call.transform[(0, 3)], draw_call.transform[(1, 3)], draw_call.transform[(2, 3)]);
+    return glm::distance2(&origin, camera_position).to_bits();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn depth(distance:f32) -> u32{
+        return (distance * distance).to_bits();
+    }
+
+    #[test]
+    fn layers_sort_before_everything_else(){
+        let low_layer = SortKey::new(0, true, depth(1.0), 9, 9, 9);
+        let high_layer = SortKey::new(1, false, depth(1.0), 0, 0, 0);
+        assert!(low_layer < high_layer);
+    }
+
+    #[test]
+    fn opaque_draws_before_transparent(){
+        let opaque = SortKey::new(0, false, depth(100.0), 9, 9, 9);
+        let transparent = SortKey::new(0, true, depth(1.0), 0, 0, 0);
+        assert!(opaque < transparent);
+    }
+
+    #[test]
+    fn opaque_groups_by_state_then_front_to_back(){
+        let near_second_pipeline = SortKey::new(0, false, depth(1.0), 2, 0, 0);
+        let far_first_pipeline = SortKey::new(0, false, depth(10.0), 1, 0, 0);
+        let near_first_pipeline = SortKey::new(0, false, depth(1.0), 1, 0, 0);
+        let mut keys = vec![near_second_pipeline, far_first_pipeline, near_first_pipeline];
+        keys.sort();
+        assert_eq!(keys, vec![near_first_pipeline, far_first_pipeline, near_second_pipeline]);
+    }
+
+    #[test]
+    fn transparent_sorts_back_to_front_regardless_of_state(){
+        let near = SortKey::new(0, true, depth(1.0), 1, 0, 0);
+        let far = SortKey::new(0, true, depth(10.0), 2, 0, 0);
+        assert!(far < near);
+    }
+
+    #[test]
+    fn depth_bits_keep_float_order(){
+        assert!(depth(0.5) < depth(1.0));
+        assert!(depth(1.0) < depth(1000.0));
+        assert_eq!(depth(0.0), 0);
+    }
+}
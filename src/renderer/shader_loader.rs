@@ -1,19 +1,79 @@
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::SystemTime;
 use vulkano::device::Device;
 use vulkano::shader::{ShaderCreationError, ShaderModule};
 use crate::renderer::ShaderType;
 
 pub struct ShaderContainer{
-    shaders: Vec<LoadedShader>
+    shaders: Vec<LoadedShader>,
+    watching: bool
 }
 
 #[derive(Clone)]
 struct LoadedShader{
     shader:Arc<ShaderModule>,
     shader_type: ShaderType,
-    name: String
+    name: String,
+    source: Option<ShaderSource>,
+    generation: u64
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderFormat{
+    Glsl,
+    SpirV
+}
+
+#[derive(Clone)]
+struct ShaderSource{
+    path: PathBuf,
+    format: ShaderFormat,
+    modified: Option<SystemTime>,
+    // Files pulled in through `#include` the last time the shader was compiled.
+    includes: Vec<IncludedFile>
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct IncludedFile{
+    path: PathBuf,
+    modified: Option<SystemTime>
+}
+
+// Magic number in the first word of every SPIR-V module.
+const SPIRV_MAGIC: u32 = 0x07230203;
+
+#[derive(Debug)]
+pub enum ShaderLoadError{
+    Io{ path: PathBuf, error: std::io::Error },
+    Compilation{ path: PathBuf, message: String },
+    Creation{ path: PathBuf, error: ShaderCreationError },
+    InvalidSpirV{ path: PathBuf, message: &'static str },
+    Pipeline{ vertex_shader: String, fragment_shader: String, message: String }
+}
+
+impl fmt::Display for ShaderLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result{
+        return match self {
+            ShaderLoadError::Io{ path, error } =>
+                write!(f, "failed to read shader {}: {}", path.display(), error),
+            ShaderLoadError::Compilation{ path, message } =>
+                write!(f, "failed to compile shader {}:\n{}", path.display(), message),
+            ShaderLoadError::Creation{ path, error } =>
+                write!(f, "failed to create shader module from {}: {}", path.display(), error),
+            ShaderLoadError::InvalidSpirV{ path, message } =>
+                write!(f, "{} is not a SPIR-V module: {}", path.display(), message),
+            ShaderLoadError::Pipeline{ vertex_shader, fragment_shader, message } =>
+                write!(f, "failed to rebuild pipeline for shaders {}/{}: {}", vertex_shader, fragment_shader, message)
+        };
+    }
+}
+
+impl std::error::Error for ShaderLoadError {}
+
 mod direct_vert {
     vulkano_shaders::shader!{
         ty: "vertex",
@@ -42,6 +102,181 @@ mod perspective_frag {
     }
 }
 
+mod textured_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/textured.vert"
+    }
+}
+
+mod textured_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/textured.frag"
+    }
+}
+
+mod instanced_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/instanced.vert"
+    }
+}
+
+mod instanced_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/instanced.frag"
+    }
+}
+
+mod blinn_phong_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/blinn_phong.vert"
+    }
+}
+
+mod blinn_phong_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/blinn_phong.frag"
+    }
+}
+
+mod pbr_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/pbr.vert"
+    }
+}
+
+mod pbr_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/pbr.frag"
+    }
+}
+
+mod shadow_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/shadow.vert"
+    }
+}
+
+mod shadow_instanced_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/shadow_instanced.vert"
+    }
+}
+
+mod shadow_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/shadow.frag"
+    }
+}
+
+mod fullscreen_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/fullscreen.vert"
+    }
+}
+
+mod deferred_lighting_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/deferred_lighting.frag"
+    }
+}
+
+mod blinn_phong_gbuffer_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/blinn_phong_gbuffer.frag"
+    }
+}
+
+mod pbr_gbuffer_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/pbr_gbuffer.frag"
+    }
+}
+
+mod post_vert {
+    vulkano_shaders::shader!{
+        ty: "vertex",
+        path : "src/shaders/post.vert"
+    }
+}
+
+mod post_copy_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_copy.frag"
+    }
+}
+
+mod post_tone_mapping_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_tone_mapping.frag"
+    }
+}
+
+mod post_bloom_threshold_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_bloom_threshold.frag"
+    }
+}
+
+mod post_bloom_blur_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_bloom_blur.frag"
+    }
+}
+
+mod post_bloom_composite_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_bloom_composite.frag"
+    }
+}
+
+mod post_fxaa_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_fxaa.frag"
+    }
+}
+
+mod post_vignette_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_vignette.frag"
+    }
+}
+
+mod post_luminance_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_luminance.frag"
+    }
+}
+
+mod post_eye_adaptation_frag {
+    vulkano_shaders::shader!{
+        ty: "fragment",
+        path : "src/shaders/post_eye_adaptation.frag"
+    }
+}
+
 impl ShaderContainer{
     pub fn load(device: Arc<Device>) -> Result<ShaderContainer, ShaderCreationError>{
         let mut loaded_shaders: Vec<LoadedShader> = Vec::new();
@@ -49,29 +284,238 @@ impl ShaderContainer{
         loaded_shaders.push(LoadedShader{
             name:String::from("direct"),
             shader_type:ShaderType::Vertex,
-            shader: direct_vert::load(device.clone())?
+            shader: direct_vert::load(device.clone())?,
+            source: None,
+            generation: 0
         });
 
         loaded_shaders.push(LoadedShader{
             name:String::from("direct"),
             shader_type:ShaderType::Fragment,
-            shader: direct_frag::load(device.clone())?
+            shader: direct_frag::load(device.clone())?,
+            source: None,
+            generation: 0
         });
 
         loaded_shaders.push(LoadedShader{
             name:String::from("perspective"),
             shader_type:ShaderType::Vertex,
-            shader: direct_vert::load(device.clone())?
+            shader: perspective_vert::load(device.clone())?,
+            source: None,
+            generation: 0
         });
 
         loaded_shaders.push(LoadedShader{
             name:String::from("perspective"),
             shader_type:ShaderType::Fragment,
-            shader: direct_frag::load(device.clone())?
+            shader: perspective_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("textured"),
+            shader_type:ShaderType::Vertex,
+            shader: textured_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("textured"),
+            shader_type:ShaderType::Fragment,
+            shader: textured_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("instanced"),
+            shader_type:ShaderType::Vertex,
+            shader: instanced_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("instanced"),
+            shader_type:ShaderType::Fragment,
+            shader: instanced_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("blinn_phong"),
+            shader_type:ShaderType::Vertex,
+            shader: blinn_phong_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("blinn_phong"),
+            shader_type:ShaderType::Fragment,
+            shader: blinn_phong_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("pbr"),
+            shader_type:ShaderType::Vertex,
+            shader: pbr_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("pbr"),
+            shader_type:ShaderType::Fragment,
+            shader: pbr_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("shadow"),
+            shader_type:ShaderType::Vertex,
+            shader: shadow_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("shadow_instanced"),
+            shader_type:ShaderType::Vertex,
+            shader: shadow_instanced_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("shadow"),
+            shader_type:ShaderType::Fragment,
+            shader: shadow_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("fullscreen"),
+            shader_type:ShaderType::Vertex,
+            shader: fullscreen_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("deferred_lighting"),
+            shader_type:ShaderType::Fragment,
+            shader: deferred_lighting_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("blinn_phong_gbuffer"),
+            shader_type:ShaderType::Fragment,
+            shader: blinn_phong_gbuffer_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("pbr_gbuffer"),
+            shader_type:ShaderType::Fragment,
+            shader: pbr_gbuffer_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post"),
+            shader_type:ShaderType::Vertex,
+            shader: post_vert::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_copy"),
+            shader_type:ShaderType::Fragment,
+            shader: post_copy_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_tone_mapping"),
+            shader_type:ShaderType::Fragment,
+            shader: post_tone_mapping_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_bloom_threshold"),
+            shader_type:ShaderType::Fragment,
+            shader: post_bloom_threshold_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_bloom_blur"),
+            shader_type:ShaderType::Fragment,
+            shader: post_bloom_blur_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_bloom_composite"),
+            shader_type:ShaderType::Fragment,
+            shader: post_bloom_composite_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_fxaa"),
+            shader_type:ShaderType::Fragment,
+            shader: post_fxaa_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_vignette"),
+            shader_type:ShaderType::Fragment,
+            shader: post_vignette_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_luminance"),
+            shader_type:ShaderType::Fragment,
+            shader: post_luminance_frag::load(device.clone())?,
+            source: None,
+            generation: 0
+        });
+
+        loaded_shaders.push(LoadedShader{
+            name:String::from("post_eye_adaptation"),
+            shader_type:ShaderType::Fragment,
+            shader: post_eye_adaptation_frag::load(device.clone())?,
+            source: None,
+            generation: 0
         });
 
         return Ok(ShaderContainer{
-            shaders:loaded_shaders});
+            shaders:loaded_shaders,
+            watching:false});
     }
 
     pub fn get_shader(&self, shader_type:ShaderType, name:&str) -> Option<Arc<ShaderModule>>{
@@ -82,4 +526,201 @@ impl ShaderContainer{
         }
         return None;
     }
-}
\ No newline at end of file
+
+    // Bumped every time a file-backed shader is successfully recompiled.
+    pub fn generation(&self, shader_type:ShaderType, name:&str) -> Option<u64>{
+        for shader in &self.shaders {
+            if shader.shader_type == shader_type && shader.name.eq(name) {
+                return Some(shader.generation);
+            }
+        }
+        return None;
+    }
+
+    // Registers a GLSL (`.vert`, `.frag`, ...) or SPIR-V (`.spv`) file, replacing any shader with the same name and type.
+    pub fn load_file(&mut self, device:Arc<Device>, shader_type:ShaderType, name:&str, path:&Path) -> Result<(), ShaderLoadError>{
+        let format = if path.extension().map_or(false, |extension| extension == "spv") {
+            ShaderFormat::SpirV
+        } else {
+            ShaderFormat::Glsl
+        };
+        let modified = modification_time(path);
+        let (module, includes) = compile_file(device, shader_type, path, format)?;
+
+        let source = ShaderSource{
+            path: path.to_path_buf(),
+            format: format,
+            modified: modified,
+            includes: includes
+        };
+
+        match self.shaders.iter_mut().find(|shader| shader.shader_type == shader_type && shader.name.eq(name)) {
+            Some(shader) => {
+                shader.shader = module;
+                shader.source = Some(source);
+                shader.generation += 1;
+            }
+            None => {
+                self.shaders.push(LoadedShader{
+                    name:String::from(name),
+                    shader_type:shader_type,
+                    shader:module,
+                    source:Some(source),
+                    generation:0
+                });
+            }
+        }
+        return Ok(());
+    }
+
+    pub fn set_watching(&mut self, watching:bool){
+        self.watching = watching;
+    }
+
+    pub fn is_watching(&self) -> bool{
+        return self.watching;
+    }
+
+    // Recompiles every file-backed shader whose file, or one of its includes, changed since it was last loaded.
+    // A shader that fails to compile keeps its previous module, so rendering carries on with the old version.
+    pub fn reload_changed(&mut self, device:Arc<Device>) -> Vec<ShaderLoadError>{
+        let mut errors: Vec<ShaderLoadError> = Vec::new();
+        for shader in &mut self.shaders {
+            let source = match &mut shader.source {
+                Some(source) => source,
+                None => continue
+            };
+
+            let modified = modification_time(&source.path);
+            let includes_changed = source.includes.iter().any(|include| modification_time(&include.path) != include.modified);
+            if modified == source.modified && !includes_changed {
+                continue;
+            }
+            source.modified = modified;
+
+            match compile_file(device.clone(), shader.shader_type, &source.path, source.format) {
+                Ok((module, includes)) => {
+                    shader.shader = module;
+                    source.includes = includes;
+                    shader.generation += 1;
+                }
+                Err(error) => {
+                    // Wait for the next edit before trying again.
+                    for include in &mut source.includes {
+                        include.modified = modification_time(&include.path);
+                    }
+                    errors.push(error);
+                }
+            }
+        }
+        return errors;
+    }
+}
+
+fn modification_time(path:&Path) -> Option<SystemTime>{
+    return fs::metadata(path).and_then(|metadata| metadata.modified()).ok();
+}
+
+fn compile_file(device:Arc<Device>, shader_type:ShaderType, path:&Path, format:ShaderFormat) -> Result<(Arc<ShaderModule>, Vec<IncludedFile>), ShaderLoadError>{
+    let (words, includes) = match format {
+        ShaderFormat::SpirV => {
+            let bytes = fs::read(path)
+                .map_err(|error| ShaderLoadError::Io{ path: path.to_path_buf(), error })?;
+            let words = spirv_words(&bytes)
+                .map_err(|message| ShaderLoadError::InvalidSpirV{ path: path.to_path_buf(), message })?;
+            (words, Vec::new())
+        }
+        ShaderFormat::Glsl => {
+            let source = fs::read_to_string(path)
+                .map_err(|error| ShaderLoadError::Io{ path: path.to_path_buf(), error })?;
+            compile_glsl(shader_type, path, &source)?
+        }
+    };
+    let module = unsafe { ShaderModule::from_words(device, &words) }
+        .map_err(|error| ShaderLoadError::Creation{ path: path.to_path_buf(), error })?;
+    return Ok((module, includes));
+}
+
+// Checked up front because the module creation functions assume well-formed words. Modules written with the other
+// byte order are swapped into native order.
+fn spirv_words(bytes:&[u8]) -> Result<Vec<u32>, &'static str>{
+    if bytes.len() % 4 != 0 {
+        return Err("length is not a multiple of four bytes");
+    }
+    let words: Vec<u32> = bytes.chunks_exact(4)
+        .map(|word| u32::from_ne_bytes([word[0], word[1], word[2], word[3]]))
+        .collect();
+    return match words.first() {
+        Some(&SPIRV_MAGIC) => Ok(words),
+        Some(&magic) if magic == SPIRV_MAGIC.swap_bytes() => Ok(words.into_iter().map(u32::swap_bytes).collect()),
+        _ => Err("missing SPIR-V magic number")
+    };
+}
+
+fn compile_glsl(shader_type:ShaderType, path:&Path, source:&str) -> Result<(Vec<u32>, Vec<IncludedFile>), ShaderLoadError>{
+    let compilation_error = |message: String| ShaderLoadError::Compilation{ path: path.to_path_buf(), message };
+
+    // Declared before the options so it outlives the include callback that borrows it.
+    let includes: RefCell<Vec<IncludedFile>> = RefCell::new(Vec::new());
+    let compiler = shaderc::Compiler::new()
+        .ok_or_else(|| compilation_error(String::from("shaderc compiler is unavailable")))?;
+    let mut options = shaderc::CompileOptions::new()
+        .ok_or_else(|| compilation_error(String::from("shaderc compile options are unavailable")))?;
+    let include_directory: PathBuf = path.parent().map(Path::to_path_buf).unwrap_or_default();
+    let recorded_includes = &includes;
+    options.set_include_callback(move |requested, _, _, _| {
+        let include_path = include_directory.join(requested);
+        // Recorded before reading, so an edit made during compilation still triggers a reload.
+        let included_file = IncludedFile{ path: include_path.clone(), modified: modification_time(&include_path) };
+        if !recorded_includes.borrow().iter().any(|include| include.path == include_path) {
+            recorded_includes.borrow_mut().push(included_file);
+        }
+        let content = fs::read_to_string(&include_path).map_err(|error| error.to_string())?;
+        return Ok(shaderc::ResolvedInclude{
+            resolved_name: include_path.to_string_lossy().into_owned(),
+            content: content
+        });
+    });
+
+    let kind = match shader_type {
+        ShaderType::Vertex => shaderc::ShaderKind::Vertex,
+        ShaderType::Fragment => shaderc::ShaderKind::Fragment
+    };
+
+    let artifact = compiler
+        .compile_into_spirv(source, kind, &path.to_string_lossy(), "main", Some(&options))
+        .map_err(|error| compilation_error(error.to_string()))?;
+    return Ok((artifact.as_binary().to_vec(), includes.take()));
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(words:&[u32]) -> Vec<u8>{
+        return words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+    }
+
+    #[test]
+    fn native_order_modules_are_kept(){
+        assert_eq!(spirv_words(&bytes(&[SPIRV_MAGIC, 0x00010000])), Ok(vec![SPIRV_MAGIC, 0x00010000]));
+    }
+
+    #[test]
+    fn swapped_modules_are_converted(){
+        let swapped = bytes(&[SPIRV_MAGIC.swap_bytes(), 0x00010000u32.swap_bytes()]);
+        assert_eq!(spirv_words(&swapped), Ok(vec![SPIRV_MAGIC, 0x00010000]));
+    }
+
+    #[test]
+    fn truncated_modules_are_rejected(){
+        let mut truncated = bytes(&[SPIRV_MAGIC, 0x00010000]);
+        truncated.pop();
+        assert!(spirv_words(&truncated).is_err());
+    }
+
+    #[test]
+    fn missing_magic_is_rejected(){
+        assert!(spirv_words(&bytes(&[0x12345678])).is_err());
+        assert!(spirv_words(&[]).is_err());
+    }
+}
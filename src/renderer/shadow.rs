@@ -0,0 +1,466 @@
+use std::sync::Arc;
+use bytemuck::{Pod, Zeroable};
+use nalgebra_glm as glm;
+use nalgebra_glm::{Mat4x4, Vec3, Vec4};
+use vulkano::device::{Device, DeviceOwned};
+use vulkano::format::Format;
+use vulkano::image::view::{ImageView, ImageViewCreateInfo, ImageViewType};
+use vulkano::image::{ImageCreateFlags, ImageDimensions, ImageUsage, StorageImage};
+use vulkano::memory::allocator::StandardMemoryAllocator;
+use vulkano::pipeline::graphics::depth_stencil::CompareOp;
+use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass};
+use vulkano::sampler::{BorderColor, Filter, Sampler, SamplerAddressMode, SamplerCreateInfo};
+use crate::camera::{Camera, Projection};
+use crate::renderer::error::RendererError;
+use crate::renderer::light::{normalize_direction, Light, LightData, MAX_LIGHTS};
+
+// Cascades and spot light shadows share one layered shadow map. Must match `MAX_SHADOW_LAYERS` in the lit shaders.
+pub const MAX_SHADOW_LAYERS: usize = 8;
+pub const MAX_CASCADES: u32 = 4;
+
+// Set 0 bindings in every shader that declares them, next to the camera and light data.
+pub const SHADOW_DATA_BINDING: u32 = 2;
+pub const SHADOW_MAP_BINDING: u32 = 3;
+
+const SHADOW_MAP_FORMAT: Format = Format::D32_SFLOAT;
+const SPOT_SHADOW_NEAR: f32 = 0.05;
+// Logarithmic splits need a positive near plane to start from.
+const MIN_SPLIT_NEAR: f32 = 0.01;
+
+// Only the first shadow casting directional light gets cascades. Spot lights take one layer each, in the order they were set.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ShadowSettings{
+    pub enabled: bool,
+    // Width and height of every shadow map layer.
+    pub resolution: u32,
+    // Clamped to `1..=MAX_CASCADES`.
+    pub cascade_count: u32,
+    // Blends between uniform (0) and logarithmic (1) cascade splits.
+    pub cascade_split_lambda: f32,
+    // Directional shadows end at this view distance, or at the camera far plane if it is closer.
+    pub max_distance: f32,
+    // Layers left after the cascades limit how many spot lights cast shadows.
+    pub max_spot_shadows: u32,
+    // Subtracted from the receiver depth to avoid shadow acne.
+    pub depth_bias: f32,
+    // Receivers are pushed this far along their normal before the lookup.
+    pub normal_offset: f32,
+    // PCF samples a (2 * radius + 1)² texel neighbourhood.
+    pub pcf_radius: u32
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self{
+        return Self{
+            enabled: true,
+            resolution: 1024,
+            cascade_count: 3,
+            cascade_split_lambda: 0.75,
+            max_distance: 50.0,
+            max_spot_shadows: 2,
+            depth_bias: 0.0005,
+            normal_offset: 0.02,
+            pcf_radius: 1
+        };
+    }
+}
+
+impl ShadowSettings {
+    pub fn disabled() -> Self{
+        return Self{
+            enabled: false,
+            ..Self::default()
+        };
+    }
+
+    pub fn resolution(mut self, resolution:u32) -> Self{
+        self.resolution = resolution.max(1);
+        return self;
+    }
+
+    pub fn cascades(mut self, cascade_count:u32, cascade_split_lambda:f32) -> Self{
+        self.cascade_count = cascade_count.clamp(1, MAX_CASCADES);
+        self.cascade_split_lambda = cascade_split_lambda.clamp(0.0, 1.0);
+        return self;
+    }
+
+    pub fn max_distance(mut self, max_distance:f32) -> Self{
+        self.max_distance = max_distance;
+        return self;
+    }
+
+    pub fn max_spot_shadows(mut self, max_spot_shadows:u32) -> Self{
+        self.max_spot_shadows = max_spot_shadows;
+        return self;
+    }
+
+    pub fn depth_bias(mut self, depth_bias:f32, normal_offset:f32) -> Self{
+        self.depth_bias = depth_bias;
+        self.normal_offset = normal_offset;
+        return self;
+    }
+
+    pub fn pcf_radius(mut self, pcf_radius:u32) -> Self{
+        self.pcf_radius = pcf_radius;
+        return self;
+    }
+
+    fn layer_count(&self) -> u32{
+        if !self.enabled {
+            return 1;
+        }
+        let cascades = self.cascade_count.clamp(1, MAX_CASCADES);
+        return (cascades + self.max_spot_shadows).min(MAX_SHADOW_LAYERS as u32);
+    }
+}
+
+// std140 layout of the `ShadowData` uniform block.
+#[repr(C)]
+#[derive(Clone, Copy, Zeroable, Pod)]
+pub(crate) struct ShadowData{
+    light_matrices: [Mat4x4; MAX_SHADOW_LAYERS],
+    // View space far distance of each cascade.
+    cascade_splits: [f32; 4],
+    // Texel size, depth bias, PCF radius, normal offset.
+    parameters: [f32; 4]
+}
+
+// One depth-only pass into a single shadow map layer.
+pub(crate) struct ShadowView{
+    pub(crate) layer: u32,
+    pub(crate) view_projection: Mat4x4
+}
+
+// Decides which lights cast shadows this frame, fits their shadow matrices and tells the light data which layers to sample.
+pub(crate) fn plan_shadows(lights:&[Light], camera:&Camera, settings:&ShadowSettings, light_data:&mut LightData) -> (Vec<ShadowView>, ShadowData){
+    let mut shadow_data = ShadowData::zeroed();
+    shadow_data.parameters = [
+        1.0 / settings.resolution.max(1) as f32,
+        settings.depth_bias,
+        settings.pcf_radius as f32,
+        settings.normal_offset
+    ];
+    let mut views: Vec<ShadowView> = Vec::new();
+    if !settings.enabled {
+        return (views, shadow_data);
+    }
+
+    let layer_count = settings.layer_count();
+    let mut has_cascades = false;
+    let mut spot_shadows: u32 = 0;
+    // Lights past `MAX_LIGHTS` are not uploaded, so they cannot be shadowed either.
+    for (index, light) in lights.iter().enumerate().take(MAX_LIGHTS) {
+        if !light.casts_shadows() {
+            continue;
+        }
+        let first_layer = views.len() as u32;
+        match *light {
+            Light::Directional{ direction, .. } if !has_cascades => {
+                let cascades = cascade_matrices(direction, camera, settings, &mut shadow_data.cascade_splits);
+                if first_layer + cascades.len() as u32 > layer_count {
+                    continue;
+                }
+                light_data.set_shadow(index, first_layer, cascades.len() as u32);
+                for (cascade, view_projection) in cascades.into_iter().enumerate() {
+                    views.push(ShadowView{ layer: first_layer + cascade as u32, view_projection: view_projection });
+                }
+                has_cascades = true;
+            }
+            Light::Spot{ position, direction, range, outer_angle, .. } if spot_shadows < settings.max_spot_shadows => {
+                if first_layer >= layer_count {
+                    continue;
+                }
+                light_data.set_shadow(index, first_layer, 1);
+                views.push(ShadowView{ layer: first_layer, view_projection: spot_matrix(position, direction, range, outer_angle) });
+                spot_shadows += 1;
+            }
+            _ => {}
+        }
+    }
+
+    for view in &views {
+        shadow_data.light_matrices[view.layer as usize] = view.view_projection;
+    }
+    return (views, shadow_data);
+}
+
+fn spot_matrix(position:Vec3, direction:Vec3, range:f32, outer_angle:f32) -> Mat4x4{
+    let direction: Vec3 = normalize_direction(direction);
+    let fov: f32 = (outer_angle * 2.0).clamp(0.01, std::f32::consts::PI - 0.01);
+    let view: Mat4x4 = glm::look_at_rh(&position, &(position + direction), &up_vector(&direction));
+    let projection: Mat4x4 = glm::perspective_rh_zo(1.0, fov, SPOT_SHADOW_NEAR, range.max(SPOT_SHADOW_NEAR * 2.0));
+    return projection * view;
+}
+
+// Splits the camera frustum between its near plane and the shadow distance, and fits one orthographic matrix around each slice.
+fn cascade_matrices(direction:Vec3, camera:&Camera, settings:&ShadowSettings, splits:&mut [f32; 4]) -> Vec<Mat4x4>{
+    let (near, far, perspective) = match camera.projection() {
+        Projection::Perspective{ near, far, .. } => (near, far, true),
+        Projection::Orthographic{ near, far, .. } => (near, far, false)
+    };
+    let shadow_far: f32 = far.min(settings.max_distance).max(near);
+    let cascade_count = settings.cascade_count.clamp(1, MAX_CASCADES) as usize;
+    // Orthographic depth precision is linear, so there is nothing to gain from logarithmic splits.
+    let lambda: f32 = if perspective { settings.cascade_split_lambda } else { 0.0 };
+    *splits = cascade_splits(near, shadow_far, cascade_count, lambda);
+
+    // Corners of the whole camera frustum, near plane first. Points in between are linear in view depth along each edge.
+    let inverse_view_projection: Mat4x4 = glm::inverse(&camera.view_projection_matrix());
+    let corners: Vec<Vec3> = [0.0, 1.0].iter()
+        .flat_map(|z| [[-1.0, -1.0], [1.0, -1.0], [1.0, 1.0], [-1.0, 1.0]].map(|[x, y]| Vec4::new(x, y, *z, 1.0)))
+        .map(|corner| {
+            let world: Vec4 = inverse_view_projection * corner;
+            world.xyz() / world.w
+        })
+        .collect();
+    let corner_at = |edge:usize, depth:f32| -> Vec3 {
+        let t = ((depth - near) / (far - near).max(f32::EPSILON)).clamp(0.0, 1.0);
+        return glm::lerp(&corners[edge], &corners[edge + 4], t);
+    };
+
+    let direction: Vec3 = normalize_direction(direction);
+    let light_rotation: Mat4x4 = glm::look_at_rh(&Vec3::zeros(), &direction, &up_vector(&direction));
+    let texel_count = settings.resolution.max(1) as f32;
+
+    let mut matrices: Vec<Mat4x4> = Vec::with_capacity(cascade_count);
+    let mut slice_near: f32 = near;
+    for &slice_far in splits.iter().take(cascade_count) {
+        let slice: Vec<Vec3> = (0..4)
+            .flat_map(|edge| [corner_at(edge, slice_near), corner_at(edge, slice_far)])
+            .collect();
+        let center: Vec3 = slice.iter().sum::<Vec3>() / slice.len() as f32;
+        // A bounding sphere keeps the cascade size constant while the camera rotates, which stops the edges from shimmering.
+        let radius: f32 = slice.iter().map(|corner| glm::distance(corner, &center)).fold(0.0, f32::max);
+        let radius: f32 = (radius * 16.0).ceil() / 16.0;
+
+        // Snapping the center to whole texels stops shadows from crawling while the camera moves.
+        let texel_size = radius * 2.0 / texel_count;
+        let light_center: Vec4 = light_rotation * Vec4::new(center.x, center.y, center.z, 1.0);
+        let x = (light_center.x / texel_size).floor() * texel_size;
+        let y = (light_center.y / texel_size).floor() * texel_size;
+        // Casters up to `max_distance` behind the slice still throw shadows into it.
+        let z_near = -light_center.z - radius - settings.max_distance;
+        let z_far = -light_center.z + radius;
+        let projection: Mat4x4 = glm::ortho_rh_zo(x - radius, x + radius, y - radius, y + radius, z_near, z_far);
+
+        matrices.push(projection * light_rotation);
+        slice_near = slice_far;
+    }
+    return matrices;
+}
+
+// View space far distance of each cascade, blending uniform (lambda 0) and logarithmic (lambda 1) splits.
+fn cascade_splits(near:f32, shadow_far:f32, cascade_count:usize, lambda:f32) -> [f32; 4]{
+    let log_near: f32 = near.max(MIN_SPLIT_NEAR);
+    let log_far: f32 = shadow_far.max(log_near);
+    let mut splits = [0.0; 4];
+    for (cascade, split) in splits.iter_mut().enumerate().take(cascade_count) {
+        let fraction = (cascade + 1) as f32 / cascade_count as f32;
+        let logarithmic = log_near * (log_far / log_near).powf(fraction);
+        let uniform = near + (shadow_far - near) * fraction;
+        *split = glm::lerp_scalar(uniform, logarithmic, lambda);
+    }
+    return splits;
+}
+
+fn up_vector(direction:&Vec3) -> Vec3{
+    return if direction.y.abs() > 0.99 { Vec3::new(0.0, 0.0, 1.0) } else { Vec3::new(0.0, 1.0, 0.0) };
+}
+
+// Per-frame layered depth image, rendered one layer at a time and sampled as an array with depth comparison.
+pub(crate) struct ShadowMap{
+    pub(crate) layer_framebuffers: Vec<Arc<Framebuffer>>,
+    pub(crate) view: Arc<ImageView<StorageImage>>,
+    pub(crate) sampler: Arc<Sampler>,
+    pub(crate) resolution: u32
+}
+
+impl ShadowMap {
+    // A disabled shadow map is a single 1x1 layer, so lit shaders can still bind it.
+    pub(crate) fn new(allocator:&StandardMemoryAllocator, render_pass:&Arc<RenderPass>, settings:&ShadowSettings, queue_family_index:u32) -> Result<Self, RendererError>{
+        let resolution: u32 = if settings.enabled { settings.resolution.max(1) } else { 1 };
+        let layer_count: u32 = settings.layer_count();
+        let image = StorageImage::with_usage(
+            allocator,
+            ImageDimensions::Dim2d {
+                width: resolution,
+                height: resolution,
+                array_layers: layer_count,
+            },
+            SHADOW_MAP_FORMAT,
+            ImageUsage {
+                depth_stencil_attachment: true,
+                sampled: true,
+                ..ImageUsage::empty()
+            },
+            ImageCreateFlags::empty(),
+            [queue_family_index],
+        ).map_err(RendererError::allocation)?;
+
+        let mut layer_framebuffers: Vec<Arc<Framebuffer>> = Vec::with_capacity(layer_count as usize);
+        for layer in 0..layer_count {
+            let mut create_info = ImageViewCreateInfo::from_image(&image);
+            create_info.view_type = ImageViewType::Dim2d;
+            create_info.subresource_range.array_layers = layer..layer + 1;
+            let layer_view = ImageView::new(image.clone(), create_info).map_err(RendererError::allocation)?;
+            layer_framebuffers.push(Framebuffer::new(
+                render_pass.clone(),
+                FramebufferCreateInfo {
+                    attachments: vec![layer_view],
+                    ..Default::default()
+                },
+            ).map_err(RendererError::allocation)?);
+        }
+
+        let mut create_info = ImageViewCreateInfo::from_image(&image);
+        create_info.view_type = ImageViewType::Dim2dArray;
+        let view = ImageView::new(image.clone(), create_info).map_err(RendererError::allocation)?;
+
+        return Ok(Self{
+            layer_framebuffers: layer_framebuffers,
+            view: view,
+            sampler: create_shadow_sampler(allocator.device().clone())?,
+            resolution: resolution
+        });
+    }
+}
+
+fn create_shadow_sampler(device:Arc<Device>) -> Result<Arc<Sampler>, RendererError>{
+    return Sampler::new(
+        device,
+        SamplerCreateInfo {
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            address_mode: [SamplerAddressMode::ClampToBorder; 3],
+            // Everything outside the map is lit.
+            border_color: BorderColor::FloatOpaqueWhite,
+            compare: Some(CompareOp::LessOrEqual),
+            ..Default::default()
+        },
+    ).map_err(RendererError::device);
+}
+
+pub(crate) fn create_shadow_render_pass(device:Arc<Device>) -> Result<Arc<RenderPass>, RendererError>{
+    return vulkano::single_pass_renderpass!(
+        device,
+        attachments: {
+            depth: {
+                load: Clear,
+                store: Store,
+                format: SHADOW_MAP_FORMAT,
+                samples: 1,
+            }
+        },
+        pass: {
+            color: [],
+            depth_stencil: {depth}
+        }).map_err(RendererError::pipeline);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sun() -> Light{
+        return Light::directional(Vec3::new(0.3, -1.0, 0.2), Vec3::new(1.0, 1.0, 1.0), 1.0).with_shadows(true);
+    }
+
+    fn spot() -> Light{
+        return Light::spot(Vec3::new(0.0, 5.0, 0.0), Vec3::new(0.0, -1.0, 0.0), Vec3::new(1.0, 1.0, 1.0), 1.0, 20.0, 0.3, 0.5).with_shadows(true);
+    }
+
+    fn plan(lights:&[Light], camera:&Camera, settings:&ShadowSettings) -> Vec<ShadowView>{
+        let mut light_data = LightData::new(lights, Vec3::zeros());
+        return plan_shadows(lights, camera, settings, &mut light_data).0;
+    }
+
+    fn is_finite(matrix:&Mat4x4) -> bool{
+        return matrix.iter().all(|value| value.is_finite());
+    }
+
+    #[test]
+    fn cascades_come_before_spot_lights(){
+        let settings = ShadowSettings::default().cascades(3, 0.5).max_spot_shadows(2);
+        let views = plan(&[sun(), spot(), spot()], &Camera::perspective(1.0, 0.1, 100.0), &settings);
+        let layers: Vec<u32> = views.iter().map(|view| view.layer).collect();
+        assert_eq!(layers, vec![0, 1, 2, 3, 4]);
+        assert!(views.iter().all(|view| is_finite(&view.view_projection)));
+    }
+
+    #[test]
+    fn spot_shadows_respect_the_limit(){
+        let settings = ShadowSettings::default().cascades(2, 0.5).max_spot_shadows(1);
+        let views = plan(&[spot(), spot(), sun(), spot()], &Camera::perspective(1.0, 0.1, 100.0), &settings);
+        let layers: Vec<u32> = views.iter().map(|view| view.layer).collect();
+        assert_eq!(layers, vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn lights_without_shadows_take_no_layers(){
+        let lights = [
+            sun().with_shadows(false),
+            Light::point(Vec3::zeros(), Vec3::new(1.0, 1.0, 1.0), 1.0, 10.0),
+            spot()
+        ];
+        let views = plan(&lights, &Camera::perspective(1.0, 0.1, 100.0), &ShadowSettings::default());
+        assert_eq!(views.len(), 1);
+        assert_eq!(views[0].layer, 0);
+    }
+
+    #[test]
+    fn layers_stay_within_the_shadow_map(){
+        let settings = ShadowSettings::default().cascades(MAX_CASCADES, 0.5).max_spot_shadows(MAX_LIGHTS as u32);
+        let mut lights = vec![spot(); MAX_LIGHTS - 1];
+        lights.insert(0, sun());
+        let views = plan(&lights, &Camera::perspective(1.0, 0.1, 100.0), &settings);
+        assert_eq!(views.len(), MAX_SHADOW_LAYERS);
+        assert!(views.iter().all(|view| (view.layer as usize) < MAX_SHADOW_LAYERS));
+    }
+
+    #[test]
+    fn disabled_shadows_plan_nothing(){
+        assert!(plan(&[sun(), spot()], &Camera::perspective(1.0, 0.1, 100.0), &ShadowSettings::disabled()).is_empty());
+    }
+
+    #[test]
+    fn zero_length_spot_direction_is_finite(){
+        assert!(is_finite(&spot_matrix(Vec3::zeros(), Vec3::zeros(), 10.0, 0.5)));
+    }
+
+    #[test]
+    fn uniform_splits_are_evenly_spaced(){
+        let splits = cascade_splits(0.0, 90.0, 3, 0.0);
+        for (split, expected) in splits.iter().zip([30.0, 60.0, 90.0, 0.0]) {
+            assert!((split - expected).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn logarithmic_splits_grow_geometrically(){
+        let splits = cascade_splits(1.0, 100.0, 2, 1.0);
+        assert!((splits[0] - 10.0).abs() < 1e-4);
+        assert!((splits[1] - 100.0).abs() < 1e-3);
+    }
+
+    #[test]
+    fn splits_without_a_positive_near_plane_are_finite(){
+        for near in [0.0, -5.0] {
+            let splits = cascade_splits(near, 50.0, 4, 0.75);
+            assert!(splits.iter().all(|split| split.is_finite()));
+            assert!(splits.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!((splits[3] - 50.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn orthographic_cascades_are_finite(){
+        let settings = ShadowSettings::default().cascades(3, 1.0);
+        let mut splits = [0.0; 4];
+        for near in [0.0, -10.0] {
+            let matrices = cascade_matrices(Vec3::new(0.0, -1.0, 0.0), &Camera::orthographic(10.0, near, 40.0), &settings, &mut splits);
+            assert_eq!(matrices.len(), 3);
+            assert!(matrices.iter().all(is_finite));
+            assert!((splits[1] - (near + (40.0 - near) * 2.0 / 3.0)).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,128 @@
+use std::sync::Arc;
+use vulkano::command_buffer::{AutoCommandBufferBuilder, CommandBufferUsage, PrimaryCommandBufferAbstract};
+use vulkano::format::Format;
+use vulkano::image::{ImageDimensions, ImmutableImage, MipmapsCount};
+use vulkano::image::view::ImageView;
+use vulkano::sampler::{Filter, LOD_CLAMP_NONE, Sampler, SamplerAddressMode, SamplerCreateInfo, SamplerMipmapMode};
+use vulkano::sync::GpuFuture;
+use crate::renderer::error::RendererError;
+use crate::renderer::gltf_loader::ImportedTexture;
+use crate::renderer::Renderer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SamplerOptions{
+    pub mag_filter: Filter,
+    pub min_filter: Filter,
+    pub mipmap_mode: SamplerMipmapMode,
+    pub address_mode: SamplerAddressMode,
+    pub anisotropy: Option<f32>
+}
+
+impl Default for SamplerOptions {
+    fn default() -> Self{
+        return Self{
+            mag_filter: Filter::Linear,
+            min_filter: Filter::Linear,
+            mipmap_mode: SamplerMipmapMode::Linear,
+            address_mode: SamplerAddressMode::Repeat,
+            anisotropy: None
+        };
+    }
+}
+
+impl SamplerOptions {
+    pub fn nearest() -> Self{
+        return Self{
+            mag_filter: Filter::Nearest,
+            min_filter: Filter::Nearest,
+            mipmap_mode: SamplerMipmapMode::Nearest,
+            ..Self::default()
+        };
+    }
+
+    pub fn address_mode(mut self, address_mode:SamplerAddressMode) -> Self{
+        self.address_mode = address_mode;
+        return self;
+    }
+
+    pub fn anisotropy(mut self, anisotropy:f32) -> Self{
+        self.anisotropy = Some(anisotropy);
+        return self;
+    }
+}
+
+#[derive(Clone)]
+pub struct Texture{
+    view: Arc<ImageView<ImmutableImage>>,
+    sampler: Arc<Sampler>
+}
+
+impl Texture {
+    pub fn from_rgba(renderer:&Renderer, width:u32, height:u32, pixels:&[u8], srgb:bool, sampler_options:SamplerOptions) -> Result<Texture, RendererError>{
+        let mut upload_builder = AutoCommandBufferBuilder::primary(
+            &renderer.command_buffer_allocator,
+            renderer.queue.queue_family_index(),
+            CommandBufferUsage::OneTimeSubmit,
+        ).map_err(RendererError::submission)?;
+
+        let image: Arc<ImmutableImage> = ImmutableImage::from_iter(
+            &renderer.allocator,
+            pixels.iter().cloned(),
+            ImageDimensions::Dim2d {
+                width: width,
+                height: height,
+                array_layers: 1,
+            },
+            MipmapsCount::Log2,
+            if srgb { Format::R8G8B8A8_SRGB } else { Format::R8G8B8A8_UNORM },
+            &mut upload_builder,
+        ).map_err(RendererError::allocation)?;
+
+        upload_builder.build().map_err(RendererError::submission)?
+            .execute(renderer.queue.clone()).map_err(RendererError::submission)?
+            .then_signal_fence_and_flush().map_err(RendererError::submission)?
+            .wait(None).map_err(RendererError::submission)?;
+
+        return Ok(Texture{
+            view: ImageView::new_default(image).map_err(RendererError::allocation)?,
+            sampler: Self::create_sampler(renderer, sampler_options)?
+        });
+    }
+
+    pub fn from_imported(renderer:&Renderer, texture:&ImportedTexture, srgb:bool, sampler_options:SamplerOptions) -> Result<Texture, RendererError>{
+        return Self::from_rgba(renderer, texture.width, texture.height, &texture.pixels, srgb, sampler_options);
+    }
+
+    fn create_sampler(renderer:&Renderer, options:SamplerOptions) -> Result<Arc<Sampler>, RendererError>{
+        let device = renderer.device.clone();
+        // Anisotropy is only honoured when the device feature was enabled, and is clamped to the device limit.
+        let anisotropy: Option<f32> = if device.enabled_features().sampler_anisotropy {
+            options.anisotropy.map(|anisotropy| {
+                anisotropy.clamp(1.0, device.physical_device().properties().max_sampler_anisotropy)
+            })
+        } else {
+            None
+        };
+
+        return Sampler::new(
+            device,
+            SamplerCreateInfo {
+                mag_filter: options.mag_filter,
+                min_filter: options.min_filter,
+                mipmap_mode: options.mipmap_mode,
+                address_mode: [options.address_mode; 3],
+                anisotropy: anisotropy,
+                lod: 0.0..=LOD_CLAMP_NONE,
+                ..Default::default()
+            },
+        ).map_err(RendererError::device);
+    }
+
+    pub fn view(&self) -> Arc<ImageView<ImmutableImage>>{
+        return self.view.clone();
+    }
+
+    pub fn sampler(&self) -> Arc<Sampler>{
+        return self.sampler.clone();
+    }
+}
\ No newline at end of file
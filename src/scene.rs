@@ -0,0 +1,461 @@
+use nalgebra_glm as glm;
+use nalgebra_glm::{Mat3, Mat4x4, Quat, Vec3};
+use crate::material::Material;
+use crate::renderer::draw_call::DrawCall;
+use crate::renderer::gltf_loader::GltfScene;
+use crate::renderer::model::Model;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform{
+    pub translation: Vec3,
+    pub rotation: Quat,
+    pub scale: Vec3
+}
+
+impl Default for Transform {
+    fn default() -> Self{
+        return Self{
+            translation: Vec3::zeros(),
+            rotation: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0)
+        };
+    }
+}
+
+impl Transform {
+    pub fn from_translation(translation:Vec3) -> Self{
+        return Self{
+            translation: translation,
+            ..Self::default()
+        };
+    }
+
+    // Assumes the matrix has no shear, which holds for the matrices glTF allows on nodes. Mirrored matrices, which
+    // glTF does allow, come out with a negative x scale.
+    pub fn from_matrix(matrix:&Mat4x4) -> Self{
+        let translation: Vec3 = matrix.fixed_slice::<3, 1>(0, 3).into();
+        let mut rotation: Mat3 = matrix.fixed_slice::<3, 3>(0, 0).into();
+        let mut scale: Vec3 = Vec3::new(
+            matrix.fixed_slice::<3, 1>(0, 0).norm(),
+            matrix.fixed_slice::<3, 1>(0, 1).norm(),
+            matrix.fixed_slice::<3, 1>(0, 2).norm());
+        // A rotation has a positive determinant, so the mirroring has to go into the scale.
+        if rotation.determinant() < 0.0 {
+            scale.x = -scale.x;
+        }
+        for column in 0..3 {
+            if scale[column] != 0.0 {
+                rotation.set_column(column, &(rotation.column(column) / scale[column]));
+            }
+        }
+        return Self{
+            translation: translation,
+            rotation: glm::mat3_to_quat(&rotation),
+            scale: scale
+        };
+    }
+
+    pub fn matrix(&self) -> Mat4x4{
+        return glm::translation(&self.translation)
+            * glm::quat_to_mat4(&self.rotation)
+            * glm::scaling(&self.scale);
+    }
+}
+
+// Stays valid until the node is removed. Ids of removed nodes are never handed out again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+#[derive(Clone)]
+pub struct Attachment{
+    pub model: Model,
+    pub material: Material
+}
+
+struct SceneNode{
+    name: Option<String>,
+    local_transform: Transform,
+    world_transform: Mat4x4,
+    // Always set on a node together with its whole subtree, so a dirty node never has clean descendants.
+    dirty: bool,
+    visible: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    attachments: Vec<Attachment>
+}
+
+// Methods given the id of a removed node do nothing and return `None`.
+pub struct SceneGraph{
+    nodes: Vec<Option<SceneNode>>,
+    roots: Vec<NodeId>
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self{
+        return Self::new();
+    }
+}
+
+impl SceneGraph {
+    pub fn new() -> Self{
+        return Self{
+            nodes: Vec::new(),
+            roots: Vec::new()
+        };
+    }
+
+    // Builds one scene node per imported node. `materials` is parallel to `scene.materials`, as in `GltfScene::draw_calls`.
+    pub fn from_gltf(scene:&GltfScene, materials:&[Material], default_material:&Material) -> Self{
+        let mut graph = Self::new();
+        let mut ids: Vec<NodeId> = Vec::with_capacity(scene.nodes.len());
+        for node in &scene.nodes {
+            // Imported parents always come before their children.
+            let parent = node.parent.map(|parent| ids[parent]);
+            let id = graph.add_node(parent, Transform::from_matrix(&node.local_transform));
+            graph.set_name(id, node.name.clone());
+            for &mesh_index in &node.meshes {
+                let mesh = &scene.meshes[mesh_index];
+                let material = mesh.material
+                    .and_then(|material| materials.get(material))
+                    .unwrap_or(default_material);
+                graph.attach(id, mesh.model.clone(), material.clone());
+            }
+            ids.push(id);
+        }
+        return graph;
+    }
+
+    pub fn add_node(&mut self, parent:Option<NodeId>, local_transform:Transform) -> NodeId{
+        let id = NodeId(self.nodes.len());
+        let parent = parent.filter(|parent| self.node(*parent).is_some());
+        self.nodes.push(Some(SceneNode{
+            name: None,
+            local_transform: local_transform,
+            world_transform: Mat4x4::identity(),
+            dirty: true,
+            visible: true,
+            parent: parent,
+            children: Vec::new(),
+            attachments: Vec::new()
+        }));
+        match parent {
+            Some(parent) => self.node_mut(parent).unwrap().children.push(id),
+            None => self.roots.push(id)
+        }
+        return id;
+    }
+
+    // Removes the node together with all of its descendants.
+    pub fn remove_node(&mut self, id:NodeId) -> Option<()>{
+        let parent = self.node(id)?.parent;
+        self.detach_from_parent(id, parent);
+        let mut pending: Vec<NodeId> = vec![id];
+        while let Some(id) = pending.pop() {
+            if let Some(node) = self.nodes[id.0].take() {
+                pending.extend(node.children);
+            }
+        }
+        return Some(());
+    }
+
+    // Re-parents the node, keeping its local transform. Fails when `parent` is the node itself or one of its descendants.
+    pub fn set_parent(&mut self, id:NodeId, parent:Option<NodeId>) -> Option<()>{
+        let old_parent = self.node(id)?.parent;
+        if let Some(parent) = parent {
+            self.node(parent)?;
+            if self.is_ancestor_or_self(id, parent) {
+                return None;
+            }
+        }
+
+        self.detach_from_parent(id, old_parent);
+        match parent {
+            Some(parent) => self.node_mut(parent)?.children.push(id),
+            None => self.roots.push(id)
+        }
+        self.node_mut(id)?.parent = parent;
+        self.mark_dirty(id);
+        return Some(());
+    }
+
+    pub fn parent(&self, id:NodeId) -> Option<NodeId>{
+        return self.node(id)?.parent;
+    }
+
+    pub fn children(&self, id:NodeId) -> &[NodeId]{
+        return self.node(id).map_or(&[], |node| &node.children);
+    }
+
+    pub fn roots(&self) -> &[NodeId]{
+        return &self.roots;
+    }
+
+    pub fn name(&self, id:NodeId) -> Option<&str>{
+        return self.node(id)?.name.as_deref();
+    }
+
+    pub fn set_name(&mut self, id:NodeId, name:Option<String>) -> Option<()>{
+        self.node_mut(id)?.name = name;
+        return Some(());
+    }
+
+    pub fn find_by_name(&self, name:&str) -> Option<NodeId>{
+        return self.nodes.iter()
+            .position(|node| node.as_ref().map_or(false, |node| node.name.as_deref() == Some(name)))
+            .map(NodeId);
+    }
+
+    pub fn local_transform(&self, id:NodeId) -> Option<Transform>{
+        return Some(self.node(id)?.local_transform);
+    }
+
+    pub fn set_local_transform(&mut self, id:NodeId, local_transform:Transform) -> Option<()>{
+        self.node_mut(id)?.local_transform = local_transform;
+        self.mark_dirty(id);
+        return Some(());
+    }
+
+    pub fn set_translation(&mut self, id:NodeId, translation:Vec3) -> Option<()>{
+        let local_transform = Transform{ translation: translation, ..self.local_transform(id)? };
+        return self.set_local_transform(id, local_transform);
+    }
+
+    pub fn set_rotation(&mut self, id:NodeId, rotation:Quat) -> Option<()>{
+        let local_transform = Transform{ rotation: glm::quat_normalize(&rotation), ..self.local_transform(id)? };
+        return self.set_local_transform(id, local_transform);
+    }
+
+    pub fn set_scale(&mut self, id:NodeId, scale:Vec3) -> Option<()>{
+        let local_transform = Transform{ scale: scale, ..self.local_transform(id)? };
+        return self.set_local_transform(id, local_transform);
+    }
+
+    // Recomputes the node's ancestors first if any of them moved since the last update.
+    pub fn world_transform(&mut self, id:NodeId) -> Option<Mat4x4>{
+        let node = self.node(id)?;
+        if node.dirty {
+            let parent_world: Mat4x4 = match node.parent {
+                Some(parent) => self.world_transform(parent)?,
+                None => Mat4x4::identity()
+            };
+            let node = self.node_mut(id)?;
+            node.world_transform = parent_world * node.local_transform.matrix();
+            node.dirty = false;
+        }
+        return Some(self.node(id)?.world_transform);
+    }
+
+    pub fn is_visible(&self, id:NodeId) -> Option<bool>{
+        return Some(self.node(id)?.visible);
+    }
+
+    // A hidden node hides its whole subtree.
+    pub fn set_visible(&mut self, id:NodeId, visible:bool) -> Option<()>{
+        self.node_mut(id)?.visible = visible;
+        return Some(());
+    }
+
+    pub fn attach(&mut self, id:NodeId, model:Model, material:Material) -> Option<()>{
+        self.node_mut(id)?.attachments.push(Attachment{ model, material });
+        return Some(());
+    }
+
+    pub fn attachments(&self, id:NodeId) -> &[Attachment]{
+        return self.node(id).map_or(&[], |node| &node.attachments);
+    }
+
+    pub fn clear_attachments(&mut self, id:NodeId) -> Option<Vec<Attachment>>{
+        return Some(std::mem::take(&mut self.node_mut(id)?.attachments));
+    }
+
+    // Brings every dirty world transform up to date in a single pass from the roots down.
+    pub fn update_transforms(&mut self){
+        let mut pending: Vec<(NodeId, Mat4x4)> = self.roots.iter()
+            .map(|root| (*root, Mat4x4::identity()))
+            .collect();
+        while let Some((id, parent_world)) = pending.pop() {
+            let node = match self.nodes[id.0].as_mut() {
+                Some(node) => node,
+                None => continue
+            };
+            if node.dirty {
+                node.world_transform = parent_world * node.local_transform.matrix();
+                node.dirty = false;
+            }
+            let world_transform = node.world_transform;
+            pending.extend(node.children.iter().map(|child| (*child, world_transform)));
+        }
+    }
+
+    // One draw call per attachment of every visible node, using up-to-date world transforms.
+    pub fn draw_calls(&mut self) -> Vec<DrawCall>{
+        self.update_transforms();
+        let mut draw_calls: Vec<DrawCall> = Vec::new();
+        let mut pending: Vec<NodeId> = self.roots.clone();
+        while let Some(id) = pending.pop() {
+            let node = match self.node(id) {
+                Some(node) if node.visible => node,
+                _ => continue
+            };
+            for attachment in &node.attachments {
+                draw_calls.push(DrawCall::new(node.world_transform, attachment.model.clone(), attachment.material.clone()));
+            }
+            pending.extend(node.children.iter().copied());
+        }
+        return draw_calls;
+    }
+
+    fn node(&self, id:NodeId) -> Option<&SceneNode>{
+        return self.nodes.get(id.0)?.as_ref();
+    }
+
+    fn node_mut(&mut self, id:NodeId) -> Option<&mut SceneNode>{
+        return self.nodes.get_mut(id.0)?.as_mut();
+    }
+
+    fn detach_from_parent(&mut self, id:NodeId, parent:Option<NodeId>){
+        match parent.and_then(|parent| self.node_mut(parent)) {
+            Some(parent) => parent.children.retain(|child| *child != id),
+            None => self.roots.retain(|root| *root != id)
+        }
+    }
+
+    fn is_ancestor_or_self(&self, ancestor:NodeId, mut id:NodeId) -> bool{
+        loop {
+            if id == ancestor {
+                return true;
+            }
+            match self.parent(id) {
+                Some(parent) => id = parent,
+                None => return false
+            }
+        }
+    }
+
+    fn mark_dirty(&mut self, id:NodeId){
+        let mut pending: Vec<NodeId> = vec![id];
+        while let Some(id) = pending.pop() {
+            if let Some(node) = self.node_mut(id) {
+                node.dirty = true;
+                pending.extend(node.children.iter().copied());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a:&Mat4x4, b:&Mat4x4){
+        assert!((a - b).abs().max() < 1e-5, "{} != {}", a, b);
+    }
+
+    fn is_dirty(graph:&SceneGraph, id:NodeId) -> bool{
+        return graph.node(id).unwrap().dirty;
+    }
+
+    #[test]
+    fn world_transform_composes_parents(){
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(None, Transform::from_translation(Vec3::new(1.0, 0.0, 0.0)));
+        let child = graph.add_node(Some(root), Transform::from_translation(Vec3::new(0.0, 2.0, 0.0)));
+        graph.set_scale(root, Vec3::new(2.0, 2.0, 2.0));
+
+        let expected = graph.local_transform(root).unwrap().matrix() * graph.local_transform(child).unwrap().matrix();
+        assert_close(&graph.world_transform(child).unwrap(), &expected);
+        assert_close(&expected, &(glm::translation(&Vec3::new(1.0, 4.0, 0.0)) * glm::scaling(&Vec3::new(2.0, 2.0, 2.0))));
+    }
+
+    #[test]
+    fn moving_a_node_marks_its_subtree_dirty(){
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(None, Transform::default());
+        let child = graph.add_node(Some(root), Transform::default());
+        let grandchild = graph.add_node(Some(child), Transform::default());
+        let sibling = graph.add_node(None, Transform::default());
+        graph.update_transforms();
+        assert!(!is_dirty(&graph, root) && !is_dirty(&graph, child) && !is_dirty(&graph, grandchild));
+
+        graph.set_translation(child, Vec3::new(0.0, 0.0, 3.0));
+        assert!(!is_dirty(&graph, root));
+        assert!(is_dirty(&graph, child));
+        assert!(is_dirty(&graph, grandchild));
+        assert!(!is_dirty(&graph, sibling));
+
+        assert_close(&graph.world_transform(grandchild).unwrap(), &glm::translation(&Vec3::new(0.0, 0.0, 3.0)));
+        assert!(!is_dirty(&graph, child) && !is_dirty(&graph, grandchild));
+    }
+
+    #[test]
+    fn update_transforms_matches_lazy_evaluation(){
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(None, Transform::from_translation(Vec3::new(0.0, 1.0, 0.0)));
+        let child = graph.add_node(Some(root), Transform{
+            rotation: glm::quat_angle_axis(glm::half_pi(), &Vec3::y()),
+            ..Transform::from_translation(Vec3::new(2.0, 0.0, 0.0))
+        });
+        let grandchild = graph.add_node(Some(child), Transform::from_translation(Vec3::new(1.0, 0.0, 0.0)));
+
+        let mut lazy = SceneGraph::new();
+        let lazy_root = lazy.add_node(None, graph.local_transform(root).unwrap());
+        let lazy_child = lazy.add_node(Some(lazy_root), graph.local_transform(child).unwrap());
+        let lazy_grandchild = lazy.add_node(Some(lazy_child), graph.local_transform(grandchild).unwrap());
+
+        graph.update_transforms();
+        assert!(!is_dirty(&graph, grandchild));
+        assert_close(&graph.node(grandchild).unwrap().world_transform, &lazy.world_transform(lazy_grandchild).unwrap());
+        // Rotated a quarter turn about y, the grandchild's x offset points down -z.
+        let position: Vec3 = graph.node(grandchild).unwrap().world_transform.fixed_slice::<3, 1>(0, 3).into();
+        assert!(glm::distance(&position, &Vec3::new(2.0, 1.0, -1.0)) < 1e-5);
+    }
+
+    #[test]
+    fn reparenting_keeps_local_transform_and_rejects_cycles(){
+        let mut graph = SceneGraph::new();
+        let a = graph.add_node(None, Transform::from_translation(Vec3::new(5.0, 0.0, 0.0)));
+        let b = graph.add_node(Some(a), Transform::from_translation(Vec3::new(1.0, 0.0, 0.0)));
+        let c = graph.add_node(None, Transform::from_translation(Vec3::new(0.0, 0.0, 7.0)));
+        graph.update_transforms();
+
+        assert!(graph.set_parent(a, Some(b)).is_none());
+        assert!(graph.set_parent(a, Some(a)).is_none());
+
+        graph.set_parent(b, Some(c)).unwrap();
+        assert_eq!(graph.children(a), &[] as &[NodeId]);
+        assert_eq!(graph.children(c), &[b]);
+        assert_close(&graph.world_transform(b).unwrap(), &glm::translation(&Vec3::new(1.0, 0.0, 7.0)));
+    }
+
+    #[test]
+    fn removing_a_node_removes_its_subtree(){
+        let mut graph = SceneGraph::new();
+        let root = graph.add_node(None, Transform::default());
+        let child = graph.add_node(Some(root), Transform::default());
+        let grandchild = graph.add_node(Some(child), Transform::default());
+
+        graph.remove_node(child).unwrap();
+        assert!(graph.world_transform(child).is_none());
+        assert!(graph.world_transform(grandchild).is_none());
+        assert!(graph.children(root).is_empty());
+        assert!(graph.remove_node(child).is_none());
+    }
+
+    #[test]
+    fn from_matrix_round_trips(){
+        let transform = Transform{
+            translation: Vec3::new(1.0, -2.0, 3.0),
+            rotation: glm::quat_angle_axis(0.7, &glm::normalize(&Vec3::new(1.0, 2.0, 0.5))),
+            scale: Vec3::new(2.0, 0.5, 3.0)
+        };
+        assert_close(&Transform::from_matrix(&transform.matrix()).matrix(), &transform.matrix());
+    }
+
+    #[test]
+    fn from_matrix_keeps_mirroring(){
+        let mirrored: Mat4x4 = glm::rotation(0.4, &Vec3::z()) * glm::scaling(&Vec3::new(1.0, -2.0, 1.0));
+        let transform = Transform::from_matrix(&mirrored);
+        assert!(transform.scale.x < 0.0);
+        assert_close(&transform.matrix(), &mirrored);
+    }
+}
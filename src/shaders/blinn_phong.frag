@@ -0,0 +1,135 @@
+#version 450
+
+#define MAX_LIGHTS 16
+#define LIGHT_DIRECTIONAL 0.0
+#define LIGHT_POINT 1.0
+
+layout(location = 0) in vec3 v_world_position;
+layout(location = 1) in vec3 v_world_normal;
+layout(location = 2) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform FrameData {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+} frame;
+
+struct Light {
+    vec4 position_range;
+    vec4 direction_type;
+    vec4 color_intensity;
+    vec4 cone;
+};
+
+layout(set = 0, binding = 1) uniform LightData {
+    Light lights[MAX_LIGHTS];
+    uvec4 count;
+    vec4 ambient;
+} light_data;
+
+#define MAX_SHADOW_LAYERS 8
+
+layout(set = 0, binding = 2) uniform ShadowData {
+    mat4 light_matrices[MAX_SHADOW_LAYERS];
+    vec4 cascade_splits;
+    // Texel size, depth bias, PCF radius, normal offset.
+    vec4 parameters;
+} shadow_data;
+
+layout(set = 0, binding = 3) uniform sampler2DArrayShadow shadow_map;
+
+layout(set = 1, binding = 0) uniform BlinnPhong {
+    vec4 diffuse_color;
+    vec3 specular_color;
+    float shininess;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D diffuse_map;
+
+// 1 when lit, 0 when fully shadowed. Lights without shadow map layers are always lit.
+float shadow_factor(Light light, vec3 world_position, vec3 normal) {
+    uint layer_count = uint(light.cone.w);
+    if (layer_count == 0) {
+        return 1.0;
+    }
+    uint layer = uint(light.cone.z);
+    if (light.direction_type.w == LIGHT_DIRECTIONAL) {
+        float view_depth = -(frame.view * vec4(world_position, 1.0)).z;
+        uint cascade = 0;
+        while (cascade < layer_count && view_depth > shadow_data.cascade_splits[cascade]) {
+            cascade++;
+        }
+        if (cascade == layer_count) {
+            return 1.0;
+        }
+        layer += cascade;
+    }
+
+    vec4 shadow_position = shadow_data.light_matrices[layer] * vec4(world_position + normal * shadow_data.parameters.w, 1.0);
+    vec3 coordinates = shadow_position.xyz / shadow_position.w;
+    if (coordinates.z > 1.0) {
+        return 1.0;
+    }
+    vec2 uv = coordinates.xy * 0.5 + 0.5;
+    float depth = coordinates.z - shadow_data.parameters.y;
+    int radius = int(shadow_data.parameters.z);
+
+    float lit = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec2 offset = vec2(x, y) * shadow_data.parameters.x;
+            lit += texture(shadow_map, vec4(uv + offset, float(layer), depth));
+        }
+    }
+    float samples = float((2 * radius + 1) * (2 * radius + 1));
+    return lit / samples;
+}
+
+// Smooth falloff that reaches exactly zero at the light range.
+float range_attenuation(float distance, float range) {
+    float ratio = distance / max(range, 0.0001);
+    float falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return falloff * falloff / (distance * distance + 1.0);
+}
+
+void main() {
+    vec4 diffuse_color = material.diffuse_color * texture(diffuse_map, v_uv);
+    vec3 normal = normalize(v_world_normal);
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    vec3 view_direction = normalize(frame.camera_position.xyz - v_world_position);
+
+    vec3 color = light_data.ambient.rgb * diffuse_color.rgb;
+    for (uint i = 0; i < min(light_data.count.x, MAX_LIGHTS); i++) {
+        Light light = light_data.lights[i];
+        float light_type = light.direction_type.w;
+
+        vec3 light_direction;
+        float attenuation = 1.0;
+        if (light_type == LIGHT_DIRECTIONAL) {
+            light_direction = -light.direction_type.xyz;
+        } else {
+            vec3 to_light = light.position_range.xyz - v_world_position;
+            float distance = length(to_light);
+            light_direction = to_light / max(distance, 0.0001);
+            attenuation = range_attenuation(distance, light.position_range.w);
+            if (light_type != LIGHT_POINT) {
+                float cos_angle = dot(-light_direction, light.direction_type.xyz);
+                attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+            }
+        }
+        attenuation *= shadow_factor(light, v_world_position, normal);
+
+        float diffuse = max(dot(normal, light_direction), 0.0);
+        vec3 half_vector = normalize(light_direction + view_direction);
+        float specular = diffuse > 0.0 ? pow(max(dot(normal, half_vector), 0.0), material.shininess) : 0.0;
+
+        vec3 radiance = light.color_intensity.rgb * light.color_intensity.w * attenuation;
+        color += radiance * (diffuse * diffuse_color.rgb + specular * material.specular_color);
+    }
+
+    f_color = vec4(color, diffuse_color.a);
+}
\ No newline at end of file
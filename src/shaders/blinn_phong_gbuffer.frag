@@ -0,0 +1,33 @@
+#version 450
+
+layout(location = 0) in vec3 v_world_position;
+layout(location = 1) in vec3 v_world_normal;
+layout(location = 2) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_emissive;
+layout(location = 1) out vec4 f_albedo;
+layout(location = 2) out vec4 f_normal;
+layout(location = 3) out vec4 f_material;
+
+layout(set = 1, binding = 0) uniform BlinnPhong {
+    vec4 diffuse_color;
+    vec3 specular_color;
+    float shininess;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D diffuse_map;
+
+void main() {
+    vec4 diffuse_color = material.diffuse_color * texture(diffuse_map, v_uv);
+    vec3 normal = normalize(v_world_normal);
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+
+    f_emissive = vec4(0.0, 0.0, 0.0, 1.0);
+    f_albedo = vec4(diffuse_color.rgb, 1.0);
+    f_normal = vec4(normal, 0.0);
+    // Lighting model 1. The G-buffer only has room for a grey specular color, and shininess up to 255.
+    float specular = dot(material.specular_color, vec3(0.2126, 0.7152, 0.0722));
+    f_material = vec4(specular, clamp(material.shininess / 255.0, 0.0, 1.0), 0.0, 1.0);
+}
\ No newline at end of file
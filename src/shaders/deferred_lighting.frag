@@ -0,0 +1,181 @@
+#version 450
+
+#define MAX_LIGHTS 16
+#define LIGHT_DIRECTIONAL 0.0
+#define LIGHT_POINT 1.0
+#define PI 3.14159265359
+
+layout(location = 0) in vec2 v_ndc;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform FrameData {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+    mat4 inverse_view_projection;
+} frame;
+
+struct Light {
+    vec4 position_range;
+    vec4 direction_type;
+    vec4 color_intensity;
+    vec4 cone;
+};
+
+layout(set = 0, binding = 1) uniform LightData {
+    Light lights[MAX_LIGHTS];
+    uvec4 count;
+    vec4 ambient;
+} light_data;
+
+#define MAX_SHADOW_LAYERS 8
+
+layout(set = 0, binding = 2) uniform ShadowData {
+    mat4 light_matrices[MAX_SHADOW_LAYERS];
+    vec4 cascade_splits;
+    // Texel size, depth bias, PCF radius, normal offset.
+    vec4 parameters;
+} shadow_data;
+
+layout(set = 0, binding = 3) uniform sampler2DArrayShadow shadow_map;
+
+layout(input_attachment_index = 0, set = 1, binding = 0) uniform subpassInput gbuffer_albedo;
+layout(input_attachment_index = 1, set = 1, binding = 1) uniform subpassInput gbuffer_normal;
+layout(input_attachment_index = 2, set = 1, binding = 2) uniform subpassInput gbuffer_material;
+layout(input_attachment_index = 3, set = 1, binding = 3) uniform subpassInput gbuffer_depth;
+
+// 1 when lit, 0 when fully shadowed. Lights without shadow map layers are always lit.
+float shadow_factor(Light light, vec3 world_position, vec3 normal) {
+    uint layer_count = uint(light.cone.w);
+    if (layer_count == 0) {
+        return 1.0;
+    }
+    uint layer = uint(light.cone.z);
+    if (light.direction_type.w == LIGHT_DIRECTIONAL) {
+        float view_depth = -(frame.view * vec4(world_position, 1.0)).z;
+        uint cascade = 0;
+        while (cascade < layer_count && view_depth > shadow_data.cascade_splits[cascade]) {
+            cascade++;
+        }
+        if (cascade == layer_count) {
+            return 1.0;
+        }
+        layer += cascade;
+    }
+
+    vec4 shadow_position = shadow_data.light_matrices[layer] * vec4(world_position + normal * shadow_data.parameters.w, 1.0);
+    vec3 coordinates = shadow_position.xyz / shadow_position.w;
+    if (coordinates.z > 1.0) {
+        return 1.0;
+    }
+    vec2 uv = coordinates.xy * 0.5 + 0.5;
+    float depth = coordinates.z - shadow_data.parameters.y;
+    int radius = int(shadow_data.parameters.z);
+
+    float lit = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec2 offset = vec2(x, y) * shadow_data.parameters.x;
+            lit += texture(shadow_map, vec4(uv + offset, float(layer), depth));
+        }
+    }
+    float samples = float((2 * radius + 1) * (2 * radius + 1));
+    return lit / samples;
+}
+
+// Smooth falloff that reaches exactly zero at the light range.
+float range_attenuation(float distance, float range) {
+    float ratio = distance / max(range, 0.0001);
+    float falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return falloff * falloff / (distance * distance + 1.0);
+}
+
+// GGX / Trowbridge-Reitz normal distribution.
+float distribution_ggx(float n_dot_h, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * denominator * denominator);
+}
+
+// Height-correlated Smith visibility term, already divided by 4 n.l n.v.
+float visibility_smith_ggx(float n_dot_l, float n_dot_v, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared);
+    float ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared);
+    float ggx = ggx_v + ggx_l;
+    return ggx > 0.0 ? 0.5 / ggx : 0.0;
+}
+
+vec3 fresnel_schlick(float v_dot_h, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+void main() {
+    float depth = subpassLoad(gbuffer_depth).r;
+    if (depth >= 1.0) {
+        discard;
+    }
+    vec4 world_position = frame.inverse_view_projection * vec4(v_ndc, depth, 1.0);
+    vec3 position = world_position.xyz / world_position.w;
+
+    vec4 albedo = subpassLoad(gbuffer_albedo);
+    vec3 normal = normalize(subpassLoad(gbuffer_normal).xyz);
+    vec4 material = subpassLoad(gbuffer_material);
+    bool blinn_phong = material.a > 0.5;
+
+    vec3 view_direction = normalize(frame.camera_position.xyz - position);
+    float n_dot_v = max(dot(normal, view_direction), 0.0001);
+
+    float metallic = material.r;
+    float roughness = max(material.g, 0.04);
+    float alpha = roughness * roughness;
+    vec3 diffuse_color = albedo.rgb * (1.0 - metallic);
+    vec3 f0 = mix(vec3(0.04), albedo.rgb, metallic);
+    float shininess = max(material.g * 255.0, 1.0);
+
+    vec3 color = blinn_phong
+        ? light_data.ambient.rgb * albedo.rgb
+        : light_data.ambient.rgb * (diffuse_color + f0) * albedo.a;
+    for (uint i = 0; i < min(light_data.count.x, MAX_LIGHTS); i++) {
+        Light light = light_data.lights[i];
+        float light_type = light.direction_type.w;
+
+        vec3 light_direction;
+        float attenuation = 1.0;
+        if (light_type == LIGHT_DIRECTIONAL) {
+            light_direction = -light.direction_type.xyz;
+        } else {
+            vec3 to_light = light.position_range.xyz - position;
+            float distance = length(to_light);
+            light_direction = to_light / max(distance, 0.0001);
+            attenuation = range_attenuation(distance, light.position_range.w);
+            if (light_type != LIGHT_POINT) {
+                float cos_angle = dot(-light_direction, light.direction_type.xyz);
+                attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+            }
+        }
+
+        float n_dot_l = max(dot(normal, light_direction), 0.0);
+        if (n_dot_l <= 0.0 || attenuation <= 0.0) {
+            continue;
+        }
+        attenuation *= shadow_factor(light, position, normal);
+        vec3 radiance = light.color_intensity.rgb * light.color_intensity.w * attenuation;
+        vec3 half_vector = normalize(light_direction + view_direction);
+        float n_dot_h = max(dot(normal, half_vector), 0.0);
+
+        if (blinn_phong) {
+            float specular = pow(n_dot_h, shininess) * material.r;
+            color += radiance * (n_dot_l * albedo.rgb + specular);
+        } else {
+            float v_dot_h = max(dot(view_direction, half_vector), 0.0);
+            vec3 fresnel = fresnel_schlick(v_dot_h, f0);
+            vec3 diffuse = (1.0 - fresnel) * diffuse_color / PI;
+            vec3 specular = fresnel * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+            color += radiance * n_dot_l * (diffuse + specular);
+        }
+    }
+
+    f_color = vec4(color, 0.0);
+}
\ No newline at end of file
@@ -2,6 +2,10 @@
 
 layout(location = 0) in vec3 position;
 
+layout(push_constant) uniform ObjectData {
+    mat4 transform;
+} object;
+
 void main() {
-    gl_Position = vec4(position, 1.0);
+    gl_Position = object.transform * vec4(position, 1.0);
 }
\ No newline at end of file
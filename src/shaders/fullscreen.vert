@@ -0,0 +1,10 @@
+#version 450
+
+layout(location = 0) out vec2 v_ndc;
+
+// One triangle covering the whole screen, generated from the vertex index without a vertex buffer.
+void main() {
+    vec2 uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    v_ndc = uv * 2.0 - 1.0;
+    gl_Position = vec4(v_ndc, 0.0, 1.0);
+}
\ No newline at end of file
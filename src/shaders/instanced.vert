@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in mat4 instance_transform;
+layout(location = 5) in vec4 instance_color;
+
+layout(location = 0) out vec4 v_color;
+
+layout(set = 0, binding = 0) uniform FrameData {
+    mat4 view;
+    mat4 projection;
+} frame;
+
+layout(push_constant) uniform ObjectData {
+    mat4 transform;
+} object;
+
+void main() {
+    v_color = instance_color;
+    gl_Position = frame.projection * frame.view * object.transform * instance_transform * vec4(position, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,204 @@
+#version 450
+
+#define MAX_LIGHTS 16
+#define LIGHT_DIRECTIONAL 0.0
+#define LIGHT_POINT 1.0
+#define PI 3.14159265359
+
+layout(location = 0) in vec3 v_world_position;
+layout(location = 1) in vec3 v_world_normal;
+layout(location = 2) in vec2 v_uv;
+layout(location = 3) in vec4 v_color;
+layout(location = 4) in vec4 v_world_tangent;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform FrameData {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+} frame;
+
+struct Light {
+    vec4 position_range;
+    vec4 direction_type;
+    vec4 color_intensity;
+    vec4 cone;
+};
+
+layout(set = 0, binding = 1) uniform LightData {
+    Light lights[MAX_LIGHTS];
+    uvec4 count;
+    vec4 ambient;
+} light_data;
+
+#define MAX_SHADOW_LAYERS 8
+
+layout(set = 0, binding = 2) uniform ShadowData {
+    mat4 light_matrices[MAX_SHADOW_LAYERS];
+    vec4 cascade_splits;
+    // Texel size, depth bias, PCF radius, normal offset.
+    vec4 parameters;
+} shadow_data;
+
+layout(set = 0, binding = 3) uniform sampler2DArrayShadow shadow_map;
+
+layout(set = 1, binding = 0) uniform Pbr {
+    vec4 base_color_factor;
+    vec3 emissive_factor;
+    float metallic_factor;
+    float roughness_factor;
+    float normal_scale;
+    float occlusion_strength;
+    float alpha_cutoff;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D base_color_map;
+layout(set = 1, binding = 2) uniform sampler2D metallic_roughness_map;
+layout(set = 1, binding = 3) uniform sampler2D normal_map;
+layout(set = 1, binding = 4) uniform sampler2D occlusion_map;
+layout(set = 1, binding = 5) uniform sampler2D emissive_map;
+
+// 1 when lit, 0 when fully shadowed. Lights without shadow map layers are always lit.
+float shadow_factor(Light light, vec3 world_position, vec3 normal) {
+    uint layer_count = uint(light.cone.w);
+    if (layer_count == 0) {
+        return 1.0;
+    }
+    uint layer = uint(light.cone.z);
+    if (light.direction_type.w == LIGHT_DIRECTIONAL) {
+        float view_depth = -(frame.view * vec4(world_position, 1.0)).z;
+        uint cascade = 0;
+        while (cascade < layer_count && view_depth > shadow_data.cascade_splits[cascade]) {
+            cascade++;
+        }
+        if (cascade == layer_count) {
+            return 1.0;
+        }
+        layer += cascade;
+    }
+
+    vec4 shadow_position = shadow_data.light_matrices[layer] * vec4(world_position + normal * shadow_data.parameters.w, 1.0);
+    vec3 coordinates = shadow_position.xyz / shadow_position.w;
+    if (coordinates.z > 1.0) {
+        return 1.0;
+    }
+    vec2 uv = coordinates.xy * 0.5 + 0.5;
+    float depth = coordinates.z - shadow_data.parameters.y;
+    int radius = int(shadow_data.parameters.z);
+
+    float lit = 0.0;
+    for (int x = -radius; x <= radius; x++) {
+        for (int y = -radius; y <= radius; y++) {
+            vec2 offset = vec2(x, y) * shadow_data.parameters.x;
+            lit += texture(shadow_map, vec4(uv + offset, float(layer), depth));
+        }
+    }
+    float samples = float((2 * radius + 1) * (2 * radius + 1));
+    return lit / samples;
+}
+
+// Smooth falloff that reaches exactly zero at the light range.
+float range_attenuation(float distance, float range) {
+    float ratio = distance / max(range, 0.0001);
+    float falloff = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return falloff * falloff / (distance * distance + 1.0);
+}
+
+// GGX / Trowbridge-Reitz normal distribution.
+float distribution_ggx(float n_dot_h, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float denominator = n_dot_h * n_dot_h * (alpha_squared - 1.0) + 1.0;
+    return alpha_squared / (PI * denominator * denominator);
+}
+
+// Height-correlated Smith visibility term, already divided by 4 n.l n.v.
+float visibility_smith_ggx(float n_dot_l, float n_dot_v, float alpha) {
+    float alpha_squared = alpha * alpha;
+    float ggx_v = n_dot_l * sqrt(n_dot_v * n_dot_v * (1.0 - alpha_squared) + alpha_squared);
+    float ggx_l = n_dot_v * sqrt(n_dot_l * n_dot_l * (1.0 - alpha_squared) + alpha_squared);
+    float ggx = ggx_v + ggx_l;
+    return ggx > 0.0 ? 0.5 / ggx : 0.0;
+}
+
+vec3 fresnel_schlick(float v_dot_h, vec3 f0) {
+    return f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+}
+
+vec3 surface_normal() {
+    vec3 normal = normalize(v_world_normal);
+    vec3 tangent = v_world_tangent.xyz - normal * dot(normal, v_world_tangent.xyz);
+    if (dot(tangent, tangent) < 0.000001) {
+        return normal;
+    }
+    tangent = normalize(tangent);
+    vec3 bitangent = cross(normal, tangent) * v_world_tangent.w;
+    vec3 tangent_normal = texture(normal_map, v_uv).xyz * 2.0 - 1.0;
+    tangent_normal.xy *= material.normal_scale;
+    return normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+}
+
+void main() {
+    vec4 base_color = material.base_color_factor * v_color * texture(base_color_map, v_uv);
+    if (base_color.a < material.alpha_cutoff) {
+        discard;
+    }
+
+    vec4 metallic_roughness = texture(metallic_roughness_map, v_uv);
+    float metallic = clamp(material.metallic_factor * metallic_roughness.b, 0.0, 1.0);
+    float roughness = clamp(material.roughness_factor * metallic_roughness.g, 0.04, 1.0);
+    float alpha = roughness * roughness;
+
+    vec3 diffuse_color = base_color.rgb * (1.0 - metallic);
+    vec3 f0 = mix(vec3(0.04), base_color.rgb, metallic);
+
+    vec3 normal = surface_normal();
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+    vec3 view_direction = normalize(frame.camera_position.xyz - v_world_position);
+    float n_dot_v = max(dot(normal, view_direction), 0.0001);
+
+    vec3 color = vec3(0.0);
+    for (uint i = 0; i < min(light_data.count.x, MAX_LIGHTS); i++) {
+        Light light = light_data.lights[i];
+        float light_type = light.direction_type.w;
+
+        vec3 light_direction;
+        float attenuation = 1.0;
+        if (light_type == LIGHT_DIRECTIONAL) {
+            light_direction = -light.direction_type.xyz;
+        } else {
+            vec3 to_light = light.position_range.xyz - v_world_position;
+            float distance = length(to_light);
+            light_direction = to_light / max(distance, 0.0001);
+            attenuation = range_attenuation(distance, light.position_range.w);
+            if (light_type != LIGHT_POINT) {
+                float cos_angle = dot(-light_direction, light.direction_type.xyz);
+                attenuation *= smoothstep(light.cone.y, light.cone.x, cos_angle);
+            }
+        }
+        attenuation *= shadow_factor(light, v_world_position, normal);
+
+        float n_dot_l = max(dot(normal, light_direction), 0.0);
+        if (n_dot_l <= 0.0 || attenuation <= 0.0) {
+            continue;
+        }
+        vec3 half_vector = normalize(light_direction + view_direction);
+        float n_dot_h = max(dot(normal, half_vector), 0.0);
+        float v_dot_h = max(dot(view_direction, half_vector), 0.0);
+
+        vec3 fresnel = fresnel_schlick(v_dot_h, f0);
+        vec3 diffuse = (1.0 - fresnel) * diffuse_color / PI;
+        vec3 specular = fresnel * distribution_ggx(n_dot_h, alpha) * visibility_smith_ggx(n_dot_l, n_dot_v, alpha);
+
+        vec3 radiance = light.color_intensity.rgb * light.color_intensity.w * attenuation;
+        color += radiance * n_dot_l * (diffuse + specular);
+    }
+
+    float occlusion = mix(1.0, texture(occlusion_map, v_uv).r, material.occlusion_strength);
+    color += light_data.ambient.rgb * (diffuse_color + f0) * occlusion;
+    color += material.emissive_factor * texture(emissive_map, v_uv).rgb;
+
+    f_color = vec4(color, base_color.a);
+}
\ No newline at end of file
@@ -0,0 +1,33 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in vec3 normal;
+layout(location = 2) in vec2 uv;
+layout(location = 3) in vec4 color;
+layout(location = 4) in vec4 tangent;
+
+layout(location = 0) out vec3 v_world_position;
+layout(location = 1) out vec3 v_world_normal;
+layout(location = 2) out vec2 v_uv;
+layout(location = 3) out vec4 v_color;
+layout(location = 4) out vec4 v_world_tangent;
+
+layout(set = 0, binding = 0) uniform FrameData {
+    mat4 view;
+    mat4 projection;
+    vec4 camera_position;
+} frame;
+
+layout(push_constant) uniform ObjectData {
+    mat4 transform;
+} object;
+
+void main() {
+    vec4 world_position = object.transform * vec4(position, 1.0);
+    v_world_position = world_position.xyz;
+    v_world_normal = mat3(transpose(inverse(object.transform))) * normal;
+    v_world_tangent = vec4(mat3(object.transform) * tangent.xyz, tangent.w);
+    v_uv = uv;
+    v_color = color;
+    gl_Position = frame.projection * frame.view * world_position;
+}
\ No newline at end of file
@@ -0,0 +1,64 @@
+#version 450
+
+layout(location = 0) in vec3 v_world_position;
+layout(location = 1) in vec3 v_world_normal;
+layout(location = 2) in vec2 v_uv;
+layout(location = 3) in vec4 v_color;
+layout(location = 4) in vec4 v_world_tangent;
+
+layout(location = 0) out vec4 f_emissive;
+layout(location = 1) out vec4 f_albedo;
+layout(location = 2) out vec4 f_normal;
+layout(location = 3) out vec4 f_material;
+
+layout(set = 1, binding = 0) uniform Pbr {
+    vec4 base_color_factor;
+    vec3 emissive_factor;
+    float metallic_factor;
+    float roughness_factor;
+    float normal_scale;
+    float occlusion_strength;
+    float alpha_cutoff;
+} material;
+
+layout(set = 1, binding = 1) uniform sampler2D base_color_map;
+layout(set = 1, binding = 2) uniform sampler2D metallic_roughness_map;
+layout(set = 1, binding = 3) uniform sampler2D normal_map;
+layout(set = 1, binding = 4) uniform sampler2D occlusion_map;
+layout(set = 1, binding = 5) uniform sampler2D emissive_map;
+
+vec3 surface_normal() {
+    vec3 normal = normalize(v_world_normal);
+    vec3 tangent = v_world_tangent.xyz - normal * dot(normal, v_world_tangent.xyz);
+    if (dot(tangent, tangent) < 0.000001) {
+        return normal;
+    }
+    tangent = normalize(tangent);
+    vec3 bitangent = cross(normal, tangent) * v_world_tangent.w;
+    vec3 tangent_normal = texture(normal_map, v_uv).xyz * 2.0 - 1.0;
+    tangent_normal.xy *= material.normal_scale;
+    return normalize(mat3(tangent, bitangent, normal) * tangent_normal);
+}
+
+void main() {
+    vec4 base_color = material.base_color_factor * v_color * texture(base_color_map, v_uv);
+    if (base_color.a < material.alpha_cutoff) {
+        discard;
+    }
+
+    vec4 metallic_roughness = texture(metallic_roughness_map, v_uv);
+    float metallic = clamp(material.metallic_factor * metallic_roughness.b, 0.0, 1.0);
+    float roughness = clamp(material.roughness_factor * metallic_roughness.g, 0.04, 1.0);
+    float occlusion = mix(1.0, texture(occlusion_map, v_uv).r, material.occlusion_strength);
+
+    vec3 normal = surface_normal();
+    if (!gl_FrontFacing) {
+        normal = -normal;
+    }
+
+    f_emissive = vec4(material.emissive_factor * texture(emissive_map, v_uv).rgb, 1.0);
+    f_albedo = vec4(base_color.rgb, occlusion);
+    f_normal = vec4(normal, 0.0);
+    // Lighting model 0, metallic-roughness.
+    f_material = vec4(metallic, roughness, 0.0, 0.0);
+}
\ No newline at end of file
@@ -2,10 +2,15 @@
 
 layout(location = 0) in vec3 position;
 
-layout(set = 0, binding = 0) uniform Data {
-    mat4 transformation;
-} uniforms;
+layout(set = 0, binding = 0) uniform FrameData {
+    mat4 view;
+    mat4 projection;
+} frame;
+
+layout(push_constant) uniform ObjectData {
+    mat4 transform;
+} object;
 
 void main() {
-    gl_Position = uniforms.transformation * vec4(position, 1.0);
+    gl_Position = frame.projection * frame.view * object.transform * vec4(position, 1.0);
 }
\ No newline at end of file
@@ -0,0 +1,9 @@
+#version 450
+
+layout(location = 0) out vec2 v_uv;
+
+// One triangle covering the whole screen, with texture coordinates running from 0 at the top left to 1 at the bottom right.
+void main() {
+    v_uv = vec2((gl_VertexIndex << 1) & 2, gl_VertexIndex & 2);
+    gl_Position = vec4(v_uv * 2.0 - 1.0, 0.0, 1.0);
+}
\ No newline at end of file
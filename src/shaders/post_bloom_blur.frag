@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+// 9-tap gaussian along parameters.xy, taken as 5 bilinear samples.
+const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+void main() {
+    vec2 step_size = post.parameters.xy * post.texel_size;
+    vec3 color = texture(source, v_uv).rgb * weights[0];
+    for (int i = 1; i < 3; i++) {
+        color += texture(source, v_uv + step_size * offsets[i]).rgb * weights[i];
+        color += texture(source, v_uv - step_size * offsets[i]).rgb * weights[i];
+    }
+    f_color = vec4(color, 1.0);
+}
\ No newline at end of file
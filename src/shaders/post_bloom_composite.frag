@@ -0,0 +1,20 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+layout(set = 0, binding = 1) uniform sampler2D bloom;
+
+// Adds the blurred highlights scaled by parameters.x.
+void main() {
+    vec4 color = texture(source, v_uv);
+    f_color = vec4(color.rgb + texture(bloom, v_uv).rgb * post.parameters.x, color.a);
+}
\ No newline at end of file
@@ -0,0 +1,26 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+// Downsamples with a 2x2 box filter and keeps only the light above parameters.x.
+void main() {
+    vec2 offset = post.texel_size * 0.5;
+    vec3 color = 0.25 * (
+        texture(source, v_uv + vec2(-offset.x, -offset.y)).rgb +
+        texture(source, v_uv + vec2(offset.x, -offset.y)).rgb +
+        texture(source, v_uv + vec2(-offset.x, offset.y)).rgb +
+        texture(source, v_uv + vec2(offset.x, offset.y)).rgb);
+
+    float brightness = max(color.r, max(color.g, color.b));
+    float contribution = max(brightness - post.parameters.x, 0.0) / max(brightness, 0.0001);
+    f_color = vec4(color * contribution, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,16 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+void main() {
+    f_color = texture(source, v_uv);
+}
\ No newline at end of file
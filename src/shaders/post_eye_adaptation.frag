@@ -0,0 +1,27 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+layout(set = 0, binding = 1) uniform sampler2D previous_luminance;
+
+// source is the 1x1 average log luminance. parameters: rate towards a brighter and towards a darker scene, minimum
+// and maximum luminance. Negative rates start from the measured luminance.
+void main() {
+    float measured = clamp(exp(texture(source, vec2(0.5)).r), post.parameters.z, post.parameters.w);
+    if (post.parameters.x < 0.0) {
+        f_color = vec4(measured, 0.0, 0.0, 1.0);
+        return;
+    }
+    float previous = texture(previous_luminance, vec2(0.5)).r;
+    float rate = measured > previous ? post.parameters.x : post.parameters.y;
+    f_color = vec4(previous + (measured - previous) * rate, 0.0, 0.0, 1.0);
+}
\ No newline at end of file
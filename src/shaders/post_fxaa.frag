@@ -0,0 +1,51 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+#define FXAA_REDUCE_MIN (1.0 / 128.0)
+#define FXAA_REDUCE_MUL (1.0 / 8.0)
+#define FXAA_SPAN_MAX 8.0
+
+float luma(vec3 color) {
+    return dot(color, vec3(0.299, 0.587, 0.114));
+}
+
+// Blurs along the edge direction estimated from the luma of the diagonal neighbours.
+void main() {
+    vec4 center = texture(source, v_uv);
+    float luma_nw = luma(texture(source, v_uv + vec2(-1.0, -1.0) * post.texel_size).rgb);
+    float luma_ne = luma(texture(source, v_uv + vec2(1.0, -1.0) * post.texel_size).rgb);
+    float luma_sw = luma(texture(source, v_uv + vec2(-1.0, 1.0) * post.texel_size).rgb);
+    float luma_se = luma(texture(source, v_uv + vec2(1.0, 1.0) * post.texel_size).rgb);
+    float luma_m = luma(center.rgb);
+
+    float luma_min = min(luma_m, min(min(luma_nw, luma_ne), min(luma_sw, luma_se)));
+    float luma_max = max(luma_m, max(max(luma_nw, luma_ne), max(luma_sw, luma_se)));
+
+    vec2 direction = vec2(
+        -((luma_nw + luma_ne) - (luma_sw + luma_se)),
+        (luma_nw + luma_sw) - (luma_ne + luma_se));
+    float direction_reduce = max((luma_nw + luma_ne + luma_sw + luma_se) * 0.25 * FXAA_REDUCE_MUL, FXAA_REDUCE_MIN);
+    float inverse_min_direction = 1.0 / (min(abs(direction.x), abs(direction.y)) + direction_reduce);
+    direction = clamp(direction * inverse_min_direction, -FXAA_SPAN_MAX, FXAA_SPAN_MAX) * post.texel_size;
+
+    vec3 color_a = 0.5 * (
+        texture(source, v_uv + direction * (1.0 / 3.0 - 0.5)).rgb +
+        texture(source, v_uv + direction * (2.0 / 3.0 - 0.5)).rgb);
+    vec3 color_b = color_a * 0.5 + 0.25 * (
+        texture(source, v_uv - direction * 0.5).rgb +
+        texture(source, v_uv + direction * 0.5).rgb);
+
+    float luma_b = luma(color_b);
+    vec3 color = (luma_b < luma_min || luma_b > luma_max) ? color_a : color_b;
+    f_color = vec4(color, center.a);
+}
\ No newline at end of file
@@ -0,0 +1,28 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+float luminance(vec3 color) {
+    return dot(color, vec3(0.2126, 0.7152, 0.0722));
+}
+
+// Log luminance, so the 1x1 average ends up as the geometric mean and a few very bright pixels do not dominate it.
+void main() {
+    // A quarter of an output texel, spreading the samples over the scene area it covers.
+    vec2 offset = fwidth(v_uv) * 0.25;
+    float sum = 0.0;
+    sum += log(max(luminance(texture(source, v_uv + vec2(-offset.x, -offset.y)).rgb), 0.0001));
+    sum += log(max(luminance(texture(source, v_uv + vec2(offset.x, -offset.y)).rgb), 0.0001));
+    sum += log(max(luminance(texture(source, v_uv + vec2(-offset.x, offset.y)).rgb), 0.0001));
+    sum += log(max(luminance(texture(source, v_uv + vec2(offset.x, offset.y)).rgb), 0.0001));
+    f_color = vec4(sum * 0.25, 0.0, 0.0, 1.0);
+}
\ No newline at end of file
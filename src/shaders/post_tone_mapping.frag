@@ -0,0 +1,34 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+layout(set = 0, binding = 1) uniform sampler2D adapted_luminance;
+
+#define REINHARD 0.0
+
+// Narkowicz 2015, fitted to the ACES reference rendering transform.
+vec3 aces(vec3 color) {
+    return clamp((color * (2.51 * color + 0.03)) / (color * (2.43 * color + 0.59) + 0.14), 0.0, 1.0);
+}
+
+// parameters.x selects the operator. parameters.y is the exposure, or with parameters.z set the key value the adapted
+// luminance is exposed to.
+void main() {
+    vec4 color = texture(source, v_uv);
+    float exposure = post.parameters.y;
+    if (post.parameters.z > 0.5) {
+        exposure /= max(texture(adapted_luminance, vec2(0.5)).r, 0.0001);
+    }
+    vec3 exposed = color.rgb * exposure;
+    vec3 mapped = post.parameters.x == REINHARD ? exposed / (exposed + 1.0) : aces(exposed);
+    f_color = vec4(mapped, color.a);
+}
\ No newline at end of file
@@ -0,0 +1,21 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 0, binding = 0) uniform sampler2D source;
+
+layout(push_constant) uniform PostParameters {
+    vec4 parameters;
+    vec2 texel_size;
+} post;
+
+// parameters: intensity, radius, smoothness.
+void main() {
+    vec4 color = texture(source, v_uv);
+    // 1 in the corners.
+    float distance = length(v_uv - 0.5) * 1.41421356;
+    float vignette = smoothstep(post.parameters.y, post.parameters.y + post.parameters.z, distance);
+    f_color = vec4(color.rgb * (1.0 - vignette * post.parameters.x), color.a);
+}
\ No newline at end of file
@@ -0,0 +1,5 @@
+#version 450
+
+// Depth only, the shadow pass has no color attachments.
+void main() {
+}
\ No newline at end of file
@@ -0,0 +1,12 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+
+// The light view-projection is premultiplied into the transform.
+layout(push_constant) uniform ObjectData {
+    mat4 transform;
+} object;
+
+void main() {
+    gl_Position = object.transform * vec4(position, 1.0);
+}
\ No newline at end of file
@@ -0,0 +1,13 @@
+#version 450
+
+layout(location = 0) in vec3 position;
+layout(location = 1) in mat4 instance_transform;
+
+// The light view-projection is premultiplied into the transform.
+layout(push_constant) uniform ObjectData {
+    mat4 transform;
+} object;
+
+void main() {
+    gl_Position = object.transform * instance_transform * vec4(position, 1.0);
+}
\ No newline at end of file
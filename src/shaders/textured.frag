@@ -0,0 +1,11 @@
+#version 450
+
+layout(location = 0) in vec2 v_uv;
+
+layout(location = 0) out vec4 f_color;
+
+layout(set = 1, binding = 0) uniform sampler2D albedo;
+
+void main() {
+    f_color = texture(albedo, v_uv);
+}
\ No newline at end of file